@@ -0,0 +1,168 @@
+//! `#[derive(HyperObject)]` for the HyperDex binding.
+//!
+//! The derive implements `ToHyperObject` and `FromHyperObject` for a struct with named
+//! fields, mapping each field to the attribute of the same name through `TryToHyperValue`
+//! and `FromHyperValue`.  Fields can be configured with `#[hyper(...)]`:
+//!
+//! * `#[hyper(rename = "name")]` stores the field under another attribute name.
+//! * `#[hyper(skip)]` leaves the field out of the object; it is set to `Default::default()`
+//!   when reading.
+//! * `#[hyper(key)]` marks the key of the space.  The key is not stored as an attribute,
+//!   but `HyperKey` is implemented to return it.  When reading, the key has to be in the
+//!   object, as it is in search results.  `get` does not return the key, so insert it into
+//!   the object first.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! #[macro_use] extern crate hyperdex_derive;
+//! extern crate hyperdex;
+//!
+//! use hyperdex::{FromHyperObject, HyperKey, ToHyperObject};
+//!
+//! #[derive(HyperObject)]
+//! struct Contact {
+//!     #[hyper(key)]
+//!     username: String,
+//!     #[hyper(rename = "first")]
+//!     first_name: Vec<u8>,
+//!     age: i64,
+//!     #[hyper(skip)]
+//!     cached: Option<i64>,
+//! }
+//!
+//! client.put(space_name, try!(contact.hyper_key()), try!(contact.to_hyper_object()));
+//!
+//! let mut obj = try!(client.get(space_name, "derek"));
+//! obj.insert("username", "derek");
+//! let contact = try!(Contact::from_hyper_object(obj));
+//! ```
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use] extern crate quote;
+
+use proc_macro::TokenStream;
+
+struct FieldSpec {
+    ident: syn::Ident,
+    attr: String,
+    key: bool,
+    skip: bool,
+}
+
+fn field_spec(field: &syn::Field) -> FieldSpec {
+    let ident = field.ident.clone().expect("#[derive(HyperObject)] requires named fields");
+    let mut spec = FieldSpec {
+        attr: ident.to_string(),
+        ident,
+        key: false,
+        skip: false,
+    };
+
+    for attr in field.attrs.iter() {
+        let items = match attr.value {
+            syn::MetaItem::List(ref name, ref items) if name == "hyper" => items,
+            _ => continue,
+        };
+        for item in items.iter() {
+            match *item {
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "key" => {
+                    spec.key = true;
+                },
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) if word == "skip" => {
+                    spec.skip = true;
+                },
+                syn::NestedMetaItem::MetaItem(
+                    syn::MetaItem::NameValue(ref name, syn::Lit::Str(ref value, _))) if name == "rename" => {
+                    spec.attr = value.clone();
+                },
+                _ => panic!("unknown #[hyper] option on field {}", spec.ident),
+            }
+        }
+    }
+
+    if spec.key && spec.skip {
+        panic!("the key field {} cannot be skipped", spec.ident);
+    }
+    spec
+}
+
+#[proc_macro_derive(HyperObject, attributes(hyper))]
+pub fn derive_hyper_object(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+
+    let fields = match ast.body {
+        syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+        _ => panic!("#[derive(HyperObject)] is only supported on structs with named fields"),
+    };
+    let specs: Vec<FieldSpec> = fields.iter().map(field_spec).collect();
+
+    let keys: Vec<&FieldSpec> = specs.iter().filter(|spec| spec.key).collect();
+    if keys.len() > 1 {
+        panic!("#[derive(HyperObject)] allows at most one #[hyper(key)] field");
+    }
+    for (i, spec) in specs.iter().enumerate() {
+        if !spec.skip && specs[..i].iter().any(|other| !other.skip && other.attr == spec.attr) {
+            panic!("more than one field is stored as attribute {}", spec.attr);
+        }
+    }
+
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let inserts = specs.iter().filter(|spec| !spec.key && !spec.skip).map(|spec| {
+        let ident = &spec.ident;
+        let attr = &spec.attr;
+        quote! { try!(obj.try_insert(#attr, self.#ident)); }
+    });
+
+    let reads = specs.iter().map(|spec| {
+        let ident = &spec.ident;
+        let attr = &spec.attr;
+        if spec.skip {
+            quote! { #ident: ::std::default::Default::default(), }
+        } else {
+            quote! { #ident: try!(obj.get(#attr)), }
+        }
+    });
+
+    let key_impl = match keys.first() {
+        Some(spec) => {
+            let ident = &spec.ident;
+            quote! {
+                impl #impl_generics ::hyperdex::HyperKey for #name #ty_generics #where_clause {
+                    fn hyper_key(&self)
+                        -> ::std::result::Result<::hyperdex::HyperValue, ::hyperdex::HyperObjectKeyError> {
+                        ::hyperdex::TryToHyperValue::try_to_hyper(::std::clone::Clone::clone(&self.#ident))
+                    }
+                }
+            }
+        },
+        None => quote! {},
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::hyperdex::ToHyperObject for #name #ty_generics #where_clause {
+            fn to_hyper_object(self)
+                -> ::std::result::Result<::hyperdex::HyperObject, ::hyperdex::HyperObjectKeyError> {
+                let mut obj = ::hyperdex::HyperObject::new();
+                #(#inserts)*
+                Ok(obj)
+            }
+        }
+
+        impl #impl_generics ::hyperdex::FromHyperObject for #name #ty_generics #where_clause {
+            fn from_hyper_object(obj: ::hyperdex::HyperObject)
+                -> ::std::result::Result<#name #ty_generics, ::hyperdex::HyperObjectKeyError> {
+                Ok(#name {
+                    #(#reads)*
+                })
+            }
+        }
+
+        #key_impl
+    };
+
+    expanded.parse().unwrap()
+}
@@ -0,0 +1,77 @@
+#[macro_use] extern crate hyperdex_derive;
+extern crate hyperdex;
+
+use std::u64;
+
+use hyperdex::*;
+use hyperdex::HyperValue::*;
+
+#[derive(HyperObject, Debug, PartialEq)]
+struct Contact {
+    #[hyper(key)]
+    username: String,
+    #[hyper(rename = "first")]
+    first_name: Vec<u8>,
+    age: i64,
+    nickname: Option<String>,
+    #[hyper(skip)]
+    cached: Option<i64>,
+}
+
+#[derive(HyperObject, Debug)]
+struct Counter {
+    #[hyper(key)]
+    id: u64,
+    hits: u64,
+}
+
+#[test]
+fn test_derive_hyper_object() {
+    let contact = Contact {
+        username: "derek".to_string(),
+        first_name: b"Derek".to_vec(),
+        age: 20,
+        nickname: None,
+        cached: Some(1),
+    };
+    assert_eq!(contact.hyper_key().unwrap(), HyperString(b"derek".to_vec()));
+
+    // The key, skipped fields and None are left out of the object
+    let mut obj = contact.to_hyper_object().unwrap();
+    assert_eq!(obj.map.len(), 2);
+    assert_eq!(obj.map.get("first"), Some(&HyperString(b"Derek".to_vec())));
+    assert_eq!(obj.map.get("age"), Some(&HyperInt(20)));
+
+    // Without the key, as when reading an object with get
+    match Contact::from_hyper_object(obj.clone()) {
+        Err(HyperObjectKeyError::KeyDoesNotExist) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    obj.insert("username", "derek");
+    let read = Contact::from_hyper_object(obj.clone()).unwrap();
+    assert_eq!(read, Contact {
+        username: "derek".to_string(),
+        first_name: b"Derek".to_vec(),
+        age: 20,
+        nickname: None,
+        cached: None,
+    });
+
+    obj.insert("age", "twenty");
+    match Contact::from_hyper_object(obj) {
+        Err(HyperObjectKeyError::ObjectIsAnotherType) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // Values that HyperDex cannot store are errors, not panics
+    let counter = Counter { id: u64::MAX, hits: u64::MAX };
+    match counter.hyper_key() {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match counter.to_hyper_object() {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -11,6 +11,7 @@ use libc::*;
 use common::*;
 use hyperdex::*;
 use hyperdex_admin::*;
+use space::ToSpaceDesc;
 
 /// A HyperDex Admin object, used for meta operations like creating and removing spaces.
 pub struct Admin {
@@ -27,15 +28,43 @@ pub struct AdminRequest {
 
 impl Admin {
     pub fn new(coordinator: SocketAddr) -> Result<Admin, String> {
-        unsafe {
+        let ip_str = match format!("{}", coordinator.ip()).to_c_str() {
+            Ok(ip_str) => ip_str,
+            Err(err) => return Err(err.message),
+        };
 
-        let ip_str = format!("{}", coordinator.ip()).to_c_str();
+        let ptr = unsafe { hyperdex_admin_create(ip_str.as_ptr(), coordinator.port()) };
+        if ptr.is_null() {
+            Err(format!("Could not create hyperdex_admin ({})", coordinator))
+        } else {
+            Ok(Admin::from_ptr(ptr))
+        }
+    }
 
-        let ptr = hyperdex_admin_create(ip_str.as_ptr(), coordinator.port());
-        let (req_tx, req_rx) = channel();
+    /// Creates a new admin from a connection string such as `"host1:1982,host2:1982"`.
+    ///
+    /// Coordinators may be given as hostnames or IPs.  The admin library takes a single
+    /// coordinator, so only the first one is used; the rest are checked but ignored.
+    pub fn from_conn_str(conn: &str) -> Result<Admin, String> {
+        let coordinators = try!(parse_conn_str(conn));
+        let (ref host, port) = coordinators[0];
+        let host_str = match host.as_str().to_c_str() {
+            Ok(host_str) => host_str,
+            Err(err) => return Err(err.message),
+        };
+        let ptr = unsafe { hyperdex_admin_create(host_str.as_ptr(), port) };
         if ptr.is_null() {
-            return Err(format!("Could not create hyperdex_admin ({})", coordinator));
+            Err(format!("Could not create hyperdex_admin ({})", conn))
         } else {
+            Ok(Admin::from_ptr(ptr))
+        }
+    }
+
+    fn from_ptr(ptr: *mut Struct_hyperdex_admin) -> Admin {
+        unsafe {
+
+        let (req_tx, req_rx) = channel();
+        {
             let ptr = Unique::new(ptr);
 
             thread::spawn(move|| {
@@ -49,19 +78,28 @@ impl Admin {
                     let mut status = 0;
                     let ret = hyperdex_admin_loop(*ptr, -1, &mut status);
                     if ret < 0 {
-                        if ret == -1 {
-                            return;
-                        } else {
-                            panic!(format!("the return code was: {}", ret));
+                        match status {
+                            HYPERDEX_ADMIN_NONEPENDING | HYPERDEX_ADMIN_TIMEOUT |
+                            HYPERDEX_ADMIN_INTERRUPTED => (),
+                            _ => {
+                                // The loop itself failed, so none of the pending requests
+                                // can complete.
+                                let err = get_admin_error(*ptr, status);
+                                for req in pending.drain() {
+                                    match req.failure {
+                                        Some(func) => func(err.clone()),
+                                        None => (),
+                                    }
+                                }
+                            },
                         }
+                        return;
                     }
-                    let req_index = pending.iter().position(|req| {
-                        if req.id == ret {
-                            true
-                        } else {
-                            false
-                        }
-                    }).unwrap();  // TODO: better error handling
+                    let req_index = match pending.iter().position(|req| req.id == ret) {
+                        Some(idx) => idx,
+                        // Not a request that we are waiting on
+                        None => return,
+                    };
                     let req = pending.remove(req_index);
 
                     if status == HYPERDEX_ADMIN_SUCCESS {
@@ -120,19 +158,24 @@ impl Admin {
             });
         }
 
-        return Ok(Admin {
+        return Admin {
             ptr: ptr,
             req_tx: req_tx,
-        })
+        }
         }
     }
 
-    pub fn add_space(&self, desc: &str) -> Result<(), HyperError> {
+    /// Creates a space from a description, which may be a string, a `SpaceDesc` or a
+    /// `SpaceBuilder`.
+    pub fn add_space<D>(&self, desc: D) -> Result<(), HyperError> where D: ToSpaceDesc {
         self.async_add_space(desc).into_inner()
     }
 
-    pub fn async_add_space(&self, desc: &str) -> Future<Result<(), HyperError>> {
-        self.async_add_or_remove_space(desc, "add")
+    pub fn async_add_space<D>(&self, desc: D) -> Future<Result<(), HyperError>> where D: ToSpaceDesc {
+        match desc.to_space_desc() {
+            Ok(desc) => self.async_add_or_remove_space(desc.as_str(), "add"),
+            Err(err) => Future::from_value(Err(err)),
+        }
     }
 
     pub fn remove_space(&self, desc: &str) -> Result<(), HyperError> {
@@ -145,7 +188,10 @@ impl Admin {
 
     fn async_add_or_remove_space(&self, desc: &str, func: &str) -> Future<Result<(), HyperError>> {
         unsafe {
-            let desc_str = desc.to_c_str();
+            let desc_str = match desc.to_c_str() {
+                Ok(desc_str) => desc_str,
+                Err(err) => return Future::from_value(Err(err)),
+            };
             let mut status_ptr = transmute(box 0u32);
             let (res_tx, res_rx) = channel();
             let req_id = match func {
@@ -182,7 +228,7 @@ impl Admin {
             self.req_tx.send(req);
 
             Future::from_fn(move|| {
-                res_rx.recv().unwrap()
+                recv_result(&res_rx)
             })
         }
     }
@@ -240,7 +286,7 @@ impl Admin {
             self.req_tx.send(req);
 
             Future::from_fn(move|| {
-                res_rx.recv().unwrap()
+                recv_result(&res_rx)
             })
         }
     }
@@ -269,7 +315,7 @@ impl Admin {
 
             self.req_tx.send(req);
 
-            res_rx.recv().unwrap()
+            recv_result(&res_rx)
         }
     }
 
@@ -297,7 +343,7 @@ impl Admin {
 
             self.req_tx.send(req);
 
-            res_rx.recv().unwrap()
+            recv_result(&res_rx)
         }
     }
 
@@ -305,7 +351,7 @@ impl Admin {
         unsafe {
             let mut status_ptr = transmute(box 0u32);
 
-            let space_str = space.to_c_str();
+            let space_str = try!(space.to_c_str());
 
             let (res_tx, res_rx) = channel();
             let req_id = hyperdex_admin_fault_tolerance(self.ptr, space_str.as_ptr(), ft, status_ptr);
@@ -327,7 +373,7 @@ impl Admin {
 
             self.req_tx.send(req);
 
-            res_rx.recv().unwrap()
+            recv_result(&res_rx)
         }
     }
 
@@ -335,7 +381,7 @@ impl Admin {
         unsafe {
             let mut status_ptr = transmute(box 0u32);
 
-            let desc_str = desc.to_c_str();
+            let desc_str = try!(desc.to_c_str());
 
             let res = hyperdex_admin_validate_space(self.ptr, desc_str.as_ptr(), status_ptr);
             if res == -1 {
@@ -349,8 +395,8 @@ impl Admin {
     pub fn hyperdex_admin_mv_space<S, T>(&self, source: S, target: T)
         -> Result<(), HyperError> where S: ToCStr, T: ToCStr {
         unsafe {
-            let source_str = source.to_c_str();
-            let target_str = target.to_c_str();
+            let source_str = try!(source.to_c_str());
+            let target_str = try!(target.to_c_str());
             let mut status_ptr = transmute(box 0u32);
             let (res_tx, res_rx) = channel();
             let req_id = hyperdex_admin_mv_space(self.ptr,
@@ -375,15 +421,15 @@ impl Admin {
 
             self.req_tx.send(req);
 
-            res_rx.recv().unwrap()
+            recv_result(&res_rx)
         }
     }
 
     pub fn hyperdex_admin_add_index<S, A>(&self, space: S, attribute: A)
         -> Result<(), HyperError> where S: ToCStr, A: ToCStr {
         unsafe {
-            let space_str = space.to_c_str();
-            let attr_str = attribute.to_c_str();
+            let space_str = try!(space.to_c_str());
+            let attr_str = try!(attribute.to_c_str());
             let mut status_ptr = transmute(box 0u32);
             let (res_tx, res_rx) = channel();
             let req_id = hyperdex_admin_add_index(self.ptr,
@@ -408,7 +454,7 @@ impl Admin {
 
             self.req_tx.send(req);
 
-            res_rx.recv().unwrap()
+            recv_result(&res_rx)
         }
     }
 
@@ -435,7 +481,7 @@ impl Admin {
 
             self.req_tx.send(req);
 
-            res_rx.recv().unwrap()
+            recv_result(&res_rx)
         }
     }
 
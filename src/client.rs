@@ -2,18 +2,22 @@
 
 extern crate errno;
 extern crate num_cpus;
+extern crate time;
 
 use std::net::SocketAddr;
 use std::sync::mpsc::TryRecvError;
-use std::collections::{HashMap, BTreeSet};
+use std::cmp;
+use std::i32;
+use std::collections::{HashMap, BTreeSet, VecDeque};
 use std::ffi::CString;
 use std::ptr::{null, null_mut, Unique};
 use std::mem::transmute;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::RawFd;
 use std::hash::Hash;
 use std::sync::atomic;
 use std::sync::atomic::Ordering;
-use std::sync::atomic::AtomicUsize;
-use std::sync::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::thread;
@@ -27,6 +31,10 @@ use hyperdex::*;
 use hyperdex_client::*;
 use hyperdex_datastructures::*;
 use client_types::*;
+use paginator::Paginator;
+use retry::RetryPolicy;
+use query::{QueryExpr, QueryStream};
+use future::{HyperFuture, event_channel, reply_channel, take_buffers};
 use client_types::HyperValue::*;
 use client_types::HyperState::*;
 
@@ -354,7 +362,10 @@ unsafe fn build_hyperobject(c_attrs: *const Struct_hyperdex_client_attribute, c_
 
             HYPERDATATYPE_DOCUMENT => {
                 let s = String::from_raw_parts(attr.value as *mut u8, (attr.value_sz - 1) as usize, (attr.value_sz - 1) as usize);
-                attrs.insert(name, Json::from_str(s.as_str()).unwrap());
+                match Json::from_str(s.as_str()) {
+                    Ok(doc) => { attrs.insert(name, doc); },
+                    Err(err) => { return Err(format!("Invalid document in attribute {}: {}", name, err)); },
+                }
             },
 
             _ => { return Err(format!("Unrecognized datatype: {}", attr.datatype)); }
@@ -384,8 +395,33 @@ unsafe fn convert_map_attributes(arena: *mut Struct_hyperdex_ds_arena, mapattrs:
     Ok(c_mapattrs)
 }
 
+/// Converts a key or value passed to a client method, so that values HyperDex cannot store,
+/// such as a `u64` above `i64::MAX`, are reported instead of panicking.
+fn to_hyper_checked<T: TryToHyperValue>(val: T) -> Result<HyperValue, HyperError> {
+    val.try_to_hyper().map_err(|err| HyperError::new(HyperErrorKind::Conversion, err))
+}
+
+unsafe fn convert_map_keys(arena: *mut Struct_hyperdex_ds_arena, attr: String, keys: Vec<HyperValue>)
+    -> Result<Vec<Struct_hyperdex_client_attribute>, String> {
+    let mut c_attrs = Vec::with_capacity(keys.len());
+    for key in keys.into_iter() {
+        let c_attr = try!(convert_cstring(arena, attr.clone()));
+        let (key_ptr, key_sz, key_ty) = try!(convert_type(arena, key));
+        c_attrs.push(Struct_hyperdex_client_attribute {
+            attr: c_attr,
+            value: key_ptr,
+            value_sz: key_sz,
+            datatype: key_ty,
+        });
+    }
+    Ok(c_attrs)
+}
+
 unsafe fn convert_cstring(arena: *mut Struct_hyperdex_ds_arena, s: String) -> Result<*const i8, String> {
-    let cstr = s.to_c_str();
+    let cstr = match s.to_c_str() {
+        Ok(cstr) => cstr,
+        Err(err) => return Err(format!("attribute name {}", err.message)),
+    };
     let mut err = 0;
     let mut cs = null();
     let mut sz = 0;
@@ -405,8 +441,7 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
     match val {
         HyperString(s) => {
             let slen = s.len() as u64;
-            let cstr = s.to_c_str();
-            if hyperdex_ds_copy_string(arena, cstr.as_ptr() as *const i8, slen,
+            if hyperdex_ds_copy_string(arena, s.as_ptr() as *const i8, slen,
                                        &mut status, &mut cs, &mut sz) < 0 {
                 mem_err
             } else {
@@ -433,9 +468,8 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                 mem_err
             } else {
                 for s in ls.into_iter() {
-                    let cstr = s.to_c_str();
-                    if hyperdex_ds_list_append_string(ds_lst, cstr.as_ptr(),
-                                                      cstr.as_bytes().len() as u64, &mut status) < 0 {
+                    if hyperdex_ds_list_append_string(ds_lst, s.as_ptr() as *const i8,
+                                                      s.len() as u64, &mut status) < 0 {
                         return mem_err;
                     }
                 }
@@ -492,9 +526,8 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                 mem_err
             } else {
                 for s in set.into_iter() {
-                    let cstr = s.to_c_str();
-                    if hyperdex_ds_set_insert_string(ds_set, cstr.as_ptr(),
-                                                     cstr.as_bytes().len() as u64, &mut status) < 0 {
+                    if hyperdex_ds_set_insert_string(ds_set, s.as_ptr() as *const i8,
+                                                     s.len() as u64, &mut status) < 0 {
                         return mem_err;
                     }
                 }
@@ -551,15 +584,13 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                 mem_err
             } else {
                 for (k, v) in map.into_iter() {
-                    let cstr = k.to_c_str();
                     if hyperdex_ds_map_insert_key_string(ds_map,
-                                                         cstr.as_ptr(), cstr.as_bytes().len() as u64,
+                                                         k.as_ptr() as *const i8, k.len() as u64,
                                                          &mut status) < 0 {
                         return mem_err;
                     }
-                    let cstr = v.to_c_str();
                     if hyperdex_ds_map_insert_val_string(ds_map,
-                                                         cstr.as_ptr(), cstr.as_bytes().len() as u64,
+                                                         v.as_ptr() as *const i8, v.len() as u64,
                                                          &mut status) < 0 {
                         return mem_err;
                     }
@@ -579,9 +610,8 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                 mem_err
             } else {
                 for (k, v) in map.into_iter() {
-                    let cstr = k.to_c_str();
                     if hyperdex_ds_map_insert_key_string(ds_map,
-                                                         cstr.as_ptr(), cstr.as_bytes().len() as u64,
+                                                         k.as_ptr() as *const i8, k.len() as u64,
                                                          &mut status) < 0 {
                         return mem_err;
                     }
@@ -604,9 +634,8 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                 mem_err
             } else {
                 for (k, v) in map.into_iter() {
-                    let cstr = k.to_c_str();
                     if hyperdex_ds_map_insert_key_string(ds_map,
-                                                         cstr.as_ptr(), cstr.as_bytes().len() as u64,
+                                                         k.as_ptr() as *const i8, k.len() as u64,
                                                          &mut status) < 0 {
                         return mem_err;
                     }
@@ -632,9 +661,8 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                     if hyperdex_ds_map_insert_key_int(ds_map, k, &mut status) < 0 {
                         return mem_err;
                     }
-                    let cstr = v.to_c_str();
                     if hyperdex_ds_map_insert_val_string(ds_map,
-                                                         cstr.as_ptr(), cstr.as_bytes().len() as u64,
+                                                         v.as_ptr() as *const i8, v.len() as u64,
                                                          &mut status) < 0 {
                         return mem_err;
                     }
@@ -701,9 +729,8 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
                     if hyperdex_ds_map_insert_key_float(ds_map, k, &mut status) < 0 {
                         return mem_err;
                     }
-                    let cstr = v.to_c_str();
                     if hyperdex_ds_map_insert_val_string(ds_map,
-                                                         cstr.as_ptr(), cstr.as_bytes().len() as u64,
+                                                         v.as_ptr() as *const i8, v.len() as u64,
                                                          &mut status) < 0 {
                         return mem_err;
                     }
@@ -764,8 +791,7 @@ unsafe fn convert_type(arena: *mut Struct_hyperdex_ds_arena, val: HyperValue) ->
         HyperDocument(doc) => {
             let s = format!("{}", doc);
             let slen = s.len() as u64;
-            let cstr = s.to_c_str();
-            if hyperdex_ds_copy_string(arena, cstr.as_ptr() as *const i8, slen,
+            if hyperdex_ds_copy_string(arena, s.as_ptr() as *const i8, slen,
                                        &mut status, &mut cs, &mut sz) < 0 {
                 mem_err
             } else {
@@ -788,6 +814,7 @@ unsafe fn convert_predicates(arena: *mut Struct_hyperdex_ds_arena, predicates: V
     -> Result<Vec<Struct_hyperdex_client_attribute_check>, String> {
     let mut res = Vec::with_capacity(predicates.len());
     for p in predicates.into_iter() {
+        try!(p.validate());
         let attr = try!(convert_cstring(arena, p.attr));
         let (val, val_sz, dt) = try!(convert_type(arena, p.value));
         res.push(Struct_hyperdex_client_attribute_check {
@@ -856,11 +883,131 @@ macro_rules! NewHyperMapAttribute(
     );
 );
 
+/** Creates a list of search predicates.
+
+Expands to a `Query`, so type errors are reported the same way as with `Query::build`.
+
+# Examples
+
+```
+let predicates = predicates!(
+    "age", LESS_EQUAL, 25;
+    "first", REGEX, "^D";
+).unwrap();
+let res = client.search(space, predicates);
+```
+**/
+#[macro_export]
+macro_rules! predicates(
+    ($($attr: expr, $pred: expr, $value: expr;)*) => (
+        Query::new()$(.attr($attr).predicate($pred, $value))*.build()
+    );
+);
+
+/// A `hyperdex_ds_arena`, destroyed when it goes out of scope so that no early return
+/// leaks it.
+struct Arena(*mut Struct_hyperdex_ds_arena);
+
+impl Arena {
+    fn new() -> Arena {
+        Arena(unsafe { hyperdex_ds_arena_create() })
+    }
+}
+
+impl Deref for Arena {
+    type Target = *mut Struct_hyperdex_ds_arena;
+
+    fn deref(&self) -> &*mut Struct_hyperdex_ds_arena {
+        &self.0
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe {
+            hyperdex_ds_arena_destroy(self.0);
+        }
+    }
+}
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+const POLLIN: c_short = 1;
+
+extern {
+    fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+}
+
+/// A pipe that wakes an inner client's event loop while it waits for HyperDex, so that the
+/// deadline of an operation started meanwhile is not noticed late.
+struct WakePipe {
+    read_fd: c_int,
+    write_fd: c_int,
+    // Whether a byte has been written that the event loop has not read yet
+    signalled: AtomicBool,
+}
+
+impl WakePipe {
+    fn new() -> Result<WakePipe, String> {
+        let mut fds = [0 as c_int; 2];
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(format!("Unable to create a pipe.  errno is: {}", errno::errno()));
+        }
+        Ok(WakePipe {
+            read_fd: fds[0],
+            write_fd: fds[1],
+            signalled: AtomicBool::new(false),
+        })
+    }
+
+    /// Makes the current or next `wait` return right away.
+    fn wake(&self) {
+        // At most two bytes are ever in the pipe, so the write never blocks
+        if !self.signalled.swap(true, Ordering::SeqCst) {
+            let byte = 0u8;
+            unsafe {
+                write(self.write_fd, &byte as *const u8 as *const c_void, 1);
+            }
+        }
+    }
+
+    /// Waits up to `timeout_ms` for `fd` to become readable or for `wake` to be called.
+    fn wait(&self, fd: c_int, timeout_ms: i32) {
+        let mut fds = [PollFd { fd: fd, events: POLLIN, revents: 0 },
+                       PollFd { fd: self.read_fd, events: POLLIN, revents: 0 }];
+        unsafe {
+            poll(fds.as_mut_ptr(), 2, timeout_ms);
+            if fds[1].revents & POLLIN != 0 {
+                self.signalled.store(false, Ordering::SeqCst);
+                let mut byte = 0u8;
+                read(self.read_fd, &mut byte as *mut u8 as *mut c_void, 1);
+            }
+        }
+    }
+}
+
+impl Drop for WakePipe {
+    fn drop(&mut self) {
+        unsafe {
+            close(self.read_fd);
+            close(self.write_fd);
+        }
+    }
+}
+
 pub struct InnerClient {
     ptr: Unique<Struct_hyperdex_client>,
     ops: Arc<Mutex<HashMap<int64_t, HyperState>>>,
     err_tx: Sender<HyperError>,
     mutex: Arc<Mutex<()>>,
+    block_ms: i32,
+    // Wakes the event loop thread; a reactor client has none
+    wake: Option<Arc<WakePipe>>,
 }
 
 impl Clone for InnerClient {
@@ -870,6 +1017,8 @@ impl Clone for InnerClient {
             ops: self.ops.clone(),
             err_tx: self.err_tx.clone(),
             mutex: self.mutex.clone(),
+            block_ms: self.block_ms,
+            wake: self.wake.clone(),
         }
     }
 }
@@ -882,11 +1031,271 @@ impl Clone for InnerClient {
     // }
 // }
 
+/// How long, in milliseconds, an event loop waits for streams to be read before it tries to
+/// hand them queued search results again.
+const FLUSH_INTERVAL_MS: u32 = 1;
+
+/// The outcome of handling one reply from HyperDex.
+enum Step {
+    /// HyperDex had nothing ready.
+    Idle,
+    /// A reply was handled.
+    Handled,
+    /// The reply belongs to a request that is not registered yet, so it was put aside.
+    Deferred,
+}
+
+/// The bookkeeping of an inner client's event loop.
+struct LoopState {
+    req_buf: Vec<(int64_t, Enum_hyperdex_client_returncode)>,
+    // Requests that timed out before HyperDex replied
+    expired: ExpiredOps,
+}
+
+impl LoopState {
+    fn new() -> LoopState {
+        LoopState {
+            req_buf: Vec::new(),
+            expired: ExpiredOps::new(),
+        }
+    }
+}
+
+/// Fails the operations and searches whose deadlines have passed by `now`, in the
+/// nanoseconds of `time::precise_time_ns`, and returns the number of milliseconds until the
+/// next deadline, if there is one.
+///
+/// An expired operation is moved to `expired` until HyperDex replies.  An expired search
+/// stays in `ops` until HyperDex reports that it is done, but its stream is ended with an
+/// error and the rest of its results are discarded.
+pub fn expire_ops(ops: &mut HashMap<int64_t, HyperState>, expired: &mut ExpiredOps, now: u64)
+    -> Option<u64> {
+    expired.age_out(now);
+
+    let mut next_ms = None;
+    let mut timed_out = Vec::new();
+    for (reqid, state) in ops.iter_mut() {
+        let deadline = match *state {
+            HyperStateOp(_, _, Some(deadline)) => deadline,
+            HyperStateSearch(ref search) if !search.done && !search.expired => {
+                match search.deadline {
+                    Some(deadline) => deadline,
+                    None => continue,
+                }
+            },
+            _ => continue,
+        };
+        if deadline > now {
+            let ms = (deadline - now) / 1_000_000 + 1;
+            next_ms = Some(next_ms.map_or(ms, |next| cmp::min(next, ms)));
+            continue;
+        }
+
+        match *state {
+            HyperStateOp(..) => timed_out.push(*reqid),
+            HyperStateSearch(ref mut search) => {
+                search.expired = true;
+                search.deliver(SearchEvent::Item(Err(deadline_exceeded("search"))));
+                search.deliver(SearchEvent::Done);
+            },
+        }
+    }
+
+    for reqid in timed_out.into_iter() {
+        if let Some(HyperStateOp(op_tx, buffers, _)) = ops.remove(&reqid) {
+            expired.insert(reqid, buffers, now);
+            op_tx.send(Err(deadline_exceeded("operation")));
+        }
+    }
+    next_ms
+}
+
+fn deadline_exceeded(what: &str) -> HyperError {
+    HyperError::new(HyperErrorKind::DeadlineExceeded,
+                    format!("the {} did not complete before its timeout", what))
+}
+
+/// The attributes that HyperDex allocates for the reply to a get.
+struct ReplyAttrs {
+    attrs: AttributePtr,
+    attrs_sz: size_t,
+}
+
+impl ReplyAttrs {
+    fn new() -> ReplyAttrs {
+        ReplyAttrs {
+            attrs: AttributePtr(null()),
+            attrs_sz: 0,
+        }
+    }
+}
+
+impl Drop for ReplyAttrs {
+    fn drop(&mut self) {
+        if !self.attrs.0.is_null() {
+            unsafe {
+                hyperdex_client_destroy_attrs(self.attrs.0, self.attrs_sz);
+            }
+        }
+    }
+}
+
+/// The search description that HyperDex allocates for the reply to `describe_search`.
+struct ReplyString(CStrPtr);
+
+impl Drop for ReplyString {
+    fn drop(&mut self) {
+        if !(self.0).0.is_null() {
+            unsafe {
+                free((self.0).0 as *mut c_void);
+            }
+        }
+    }
+}
+
 impl InnerClient {
 
+    /// Fails the operations and searches whose deadlines have passed and returns the number
+    /// of milliseconds until the next deadline, if there is one.
+    fn expire_ops(&self, expired: &mut ExpiredOps) -> Option<u64> {
+        let mut ops = self.ops.lock().unwrap();
+        expire_ops(&mut *ops, expired, time::precise_time_ns())
+    }
+
+    /// Hands queued search results to their streams and forgets the searches that are over.
+    ///
+    /// Returns whether any results are still queued, and whether pulling replies from
+    /// HyperDex should wait because a stream is too far behind and no operation is waiting.
+    fn flush_searches(&self) -> (bool, bool) {
+        let mut ops = self.ops.lock().unwrap();
+        let mut queued = false;
+        let mut full = false;
+        let mut waiting = false;
+        let mut finished = Vec::new();
+        for (reqid, state) in ops.iter_mut() {
+            match *state {
+                HyperStateOp(..) => waiting = true,
+                HyperStateSearch(ref mut search) => {
+                    search.flush();
+                    queued = queued || !search.overflow.is_empty();
+                    full = full || search.is_full();
+                    if search.is_finished() {
+                        finished.push(*reqid);
+                    }
+                },
+            }
+        }
+
+        for reqid in finished.iter() {
+            ops.remove(reqid);
+        }
+        (queued, full && !waiting)
+    }
+
+    /// Handles the next reply from HyperDex without blocking.
+    ///
+    /// Search results are queued on their search and handed to the stream only as far as
+    /// its buffer allows, so this never waits for a stream to be read.
+    unsafe fn step(&self, loop_state: &mut LoopState) -> Step {
+        let mut reqid = 0;
+        let mut loop_status = 0;
+        let _lockhandle = self.mutex.lock();
+
+        // Replies that arrived before their request was registered are handled first
+        let buffered = {
+            let ops = self.ops.lock().unwrap();
+            loop_state.req_buf.iter().position(|&(r, _)| ops.contains_key(&r))
+        };
+        match buffered {
+            Some(idx) => {
+                let (r, l) = loop_state.req_buf.remove(idx);
+                reqid = r;
+                loop_status = l;
+            },
+            None => {
+                reqid = hyperdex_client_loop(*self.ptr, 0, &mut loop_status);
+            },
+        }
+
+        if reqid < 0 && loop_status == HYPERDEX_CLIENT_TIMEOUT {
+            return Step::Idle;
+        } else if reqid < 0 && loop_status == HYPERDEX_CLIENT_NONEPENDING {
+            return Step::Idle;
+        } else if reqid < 0 {
+            self.err_tx.send(get_client_error(*self.ptr, loop_status));
+        } else if loop_state.expired.reply(reqid) {
+            // The caller has already been told that this request timed out
+        } else {
+            let mut ops = self.ops.lock().unwrap();
+            let mut remove_req = false;
+            let mut op_done = false;
+            match ops.get_mut(&reqid) {
+                None => {
+                    // This is a very rare race condition.  It happens when the request
+                    // completes before the corresponding SearchState is inserted into
+                    // the hashmap.
+                    loop_state.req_buf.push((reqid, loop_status));
+                    return Step::Deferred;
+                },
+
+                Some(&mut HyperStateOp(..)) => {
+                    op_done = true;
+                },
+
+                Some(&mut HyperStateSearch(ref mut state)) => {
+                    let cancelled = state.cancelled.load(Ordering::Relaxed) || state.expired;
+                    if *state.status == HYPERDEX_CLIENT_SUCCESS {
+                        if !cancelled {
+                            let res = match build_hyperobject((*state.attrs).0, *state.attrs_sz) {
+                                Ok(attrs) => Ok(attrs),
+                                Err(err) => Err(HyperError::new(HyperErrorKind::Conversion, err)),
+                            };
+                            state.deliver(SearchEvent::Item(res));
+                        }
+                        hyperdex_client_destroy_attrs((*state.attrs).0, *state.attrs_sz);
+                    } else if *state.status == HYPERDEX_CLIENT_SEARCHDONE {
+                        state.done = true;
+                        if !cancelled {
+                            state.deliver(SearchEvent::Done);
+                        }
+                        remove_req = state.is_finished();
+                    } else if !cancelled {
+                        let err = get_client_error(*self.ptr, *state.status);
+                        state.deliver(SearchEvent::Item(Err(err)));
+                    }
+                },
+            }
+            if op_done {
+                // Hand the buffers back with the outcome
+                if let Some(HyperStateOp(op_tx, buffers, _)) = ops.remove(&reqid) {
+                    if loop_status == HYPERDEX_CLIENT_SUCCESS {
+                        op_tx.send(Ok(buffers));
+                    } else {
+                        op_tx.send(Err(get_client_error(*self.ptr, loop_status)));
+                    }
+                }
+            } else if remove_req {
+                ops.remove(&reqid);
+            }
+        }
+        Step::Handled
+    }
+
+    /// Waits up to `timeout_ms` for HyperDex to have a reply ready, or for an operation with
+    /// a deadline to be started.
+    unsafe fn block(&self, timeout_ms: i32) {
+        match self.wake {
+            Some(ref wake) => wake.wait(hyperdex_client_poll(*self.ptr), timeout_ms),
+            None => {
+                hyperdex_client_block(*self.ptr, timeout_ms);
+            },
+        }
+    }
+
     fn run_forever(&mut self, shutdown_rx: Receiver<()>) {
         unsafe {
-            let mut req_buf = Vec::new();
+            let mut loop_state = LoopState::new();
+            let mut block_ms = self.block_ms;
             loop {
                 match shutdown_rx.try_recv() {
                     Err(TryRecvError::Empty) => (),
@@ -897,73 +1306,22 @@ impl InnerClient {
                     }
                 }
 
-                hyperdex_client_block(*self.ptr, 250);  // prevent busy spinning
-
-                let mut reqid = 0;
-                let mut loop_status = 0;
-                let _lockhandle = self.mutex.lock();
-
-                match req_buf.pop() {
-                    Some((r, l)) => {
-                        reqid = r;
-                        loop_status = l;
-                    },
-                    None => {
-                        reqid = hyperdex_client_loop(*self.ptr, 0, &mut loop_status);
-                    },
-                }
-
-                if reqid < 0 && loop_status == HYPERDEX_CLIENT_TIMEOUT {
-                    // pass
-                } else if reqid < 0 && loop_status == HYPERDEX_CLIENT_NONEPENDING {
-                    // pass
-                } else if reqid < 0 {
-                    self.err_tx.send(get_client_error(*self.ptr, loop_status));
+                let (queued, paused) = self.flush_searches();
+                if paused {
+                    // Wait for the streams to be read instead of pulling more results
+                    thread::sleep_ms(FLUSH_INTERVAL_MS);
                 } else {
-                    let mut ops = &mut*self.ops.lock().unwrap();
-                    let mut remove_req = false;
-                    match ops.get(&reqid) {
-                        None => {
-                            // This is a very rare race condition.  It happens when the request
-                            // completes before the corresponding SearchState is inserted into
-                            // the hashmap.
-                            req_buf.push((reqid, loop_status));
-                        },
-
-                        Some(&HyperStateOp(ref op_tx)) => {
-                            op_tx.send(get_client_error(*self.ptr, loop_status));
-                            remove_req = true;
-                        },
-
-                        Some(&HyperStateSearch(ref state)) => {
-                            if *state.status == HYPERDEX_CLIENT_SUCCESS {
-                                match build_hyperobject((*state.attrs).0, *state.attrs_sz) {
-                                    Ok(attrs) => {
-                                        state.res_tx.send(Ok(attrs));
-                                    },
-                                    Err(err) => {
-                                        let herr = HyperError {
-                                            status: HYPERDEX_CLIENT_SERVERERROR,
-                                            message: err,
-                                            location: String::new(),
-                                        };
-                                        state.res_tx.send(Err(herr));
-                                    }
-                                }
-                                hyperdex_client_destroy_attrs((*state.attrs).0, *state.attrs_sz);
-                            } else if *state.status == HYPERDEX_CLIENT_SEARCHDONE {
-                                remove_req = true;
-                                // this seems to be a bug in Rust... state.res_tx sometimes
-                                // doesn't get dropped properly
-                            } else {
-                                state.res_tx.send(Err(get_client_error(*self.ptr, *state.status)));
-                            }
-                        },
-                    }
-                    if remove_req {
-                        ops.remove(&reqid);
+                    if queued {
+                        block_ms = cmp::min(block_ms, FLUSH_INTERVAL_MS as i32);
                     }
+                    self.block(block_ms);  // prevent busy spinning
+                    self.step(&mut loop_state);
                 }
+
+                block_ms = match self.expire_ops(&mut loop_state.expired) {
+                    Some(ms) => cmp::min(self.block_ms as u64, ms) as i32,
+                    None => self.block_ms,
+                };
             }
         }
     }
@@ -973,21 +1331,32 @@ macro_rules! make_fn_spacename_key_status_attributes(
     ($fn_name: ident, $async_name: ident) => (
         impl Client {
         pub fn $async_name<S, K>(&mut self, space: S, key: K)
-            -> Future<Result<HyperObject, HyperError>> where S: ToCStr, K: ToHyperValue {
+            -> HyperFuture<HyperObject> where S: ToCStr, K: TryToHyperValue {
             unsafe {
             // TODO: Is "Relaxed" good enough?
             let inner_client =
                 self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-            let arena = hyperdex_ds_arena_create();
-            let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
-            let space_str = space.to_c_str();
+            let key = match to_hyper_checked(key) {
+                Ok(key) => key,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+            let arena = Arena::new();
+            let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                Ok(x) => x,
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                },
+            };
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
 
             let mut status = box 0u32;
-            let mut attrs = box AttributePtr(null());
-            let mut attrs_sz = box 0u64;
+            let mut reply = box ReplyAttrs::new();
 
-            let (err_tx, err_rx) = channel();
+            let (err_tx, err_rx) = reply_channel();
 
             {
                 let _lockhandle = inner_client.mutex.lock();
@@ -999,63 +1368,69 @@ macro_rules! make_fn_spacename_key_status_attributes(
                                                                key_str,
                                                                key_sz,
                                                                &mut *status,
-                                                               &mut (*attrs).0, &mut *attrs_sz);
+                                                               &mut reply.attrs.0, &mut reply.attrs_sz);
                 if req_id < 0 {
-                    return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status)));
                 }
-                ops.insert(req_id, HyperStateOp(err_tx));
+                ops.insert(req_id, HyperStateOp(err_tx, box (status, reply), self.deadline(&inner_client)));
             }
-            hyperdex_ds_arena_destroy(arena);
 
-            Future::from_fn(move|| {
-                let err = err_rx.recv().unwrap();
-                if err.status != HYPERDEX_CLIENT_SUCCESS {
-                    Err(err)
-                } else if *status != HYPERDEX_CLIENT_SUCCESS {
+            HyperFuture::new(err_rx, move|buffers| {
+                let (status, reply): (Box<Enum_hyperdex_client_returncode>, Box<ReplyAttrs>) =
+                    try!(take_buffers(buffers));
+                if *status != HYPERDEX_CLIENT_SUCCESS {
                     Err(get_client_error(*inner_client.ptr, *status))
                 } else {
-                    let res = match build_hyperobject((*attrs).0, *attrs_sz) {
+                    match build_hyperobject(reply.attrs.0, reply.attrs_sz) {
                         Ok(obj) => {
                             Ok(obj)
                         },
                         Err(msg) => {
-                            Err(HyperError {
-                                status: HYPERDEX_CLIENT_SERVERERROR,
-                                message: msg,
-                                location: String::new(),
-                            })
+                            Err(HyperError::new(HyperErrorKind::Conversion, msg))
                         }
-                    };
-                    hyperdex_client_destroy_attrs((*attrs).0, *attrs_sz);
-                    res
+                    }
                 }
             })
             }
         }
 
         pub fn $fn_name<S, K>(&mut self, space: S, key: K)
-            -> Result<HyperObject, HyperError> where S: ToCStr, K: ToHyperValue {
-            self.$async_name(space, key).into_inner()
+            -> Result<HyperObject, HyperError> where S: ToCStr, K: TryToHyperValue {
+            let space = try!(space.to_c_str()).into_bytes();
+            let key = try!(to_hyper_checked(key));
+            self.retry(true, |client| client.$async_name(space.clone(), key.clone()))
         }
         }
     );
 );
 
 macro_rules! make_fn_spacename_key_status(
-    ($fn_name: ident, $async_name: ident) => (
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
         impl Client {
         pub fn $async_name<S, K>(&mut self, space: S, key: K)
-            -> Future<Result<(), HyperError>> where S: ToCStr, K: ToHyperValue {
+            -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue {
             unsafe {
             let inner_client =
                 self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-            let arena = hyperdex_ds_arena_create();
-            let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
-            let space_str = space.to_c_str();
+            let key = match to_hyper_checked(key) {
+                Ok(key) => key,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+            let arena = Arena::new();
+            let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                Ok(x) => x,
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                },
+            };
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
             let mut status = box 0u32;
 
-            let (err_tx, err_rx) = channel();
+            let (err_tx, err_rx) = reply_channel();
 
             {
                 let _lockhandle = inner_client.mutex.lock();
@@ -1067,17 +1442,14 @@ macro_rules! make_fn_spacename_key_status(
                                                                key_str, key_sz,
                                                                &mut *status);
                 if req_id < 0 {
-                    return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status)));
                 }
-                ops.insert(req_id, HyperStateOp(err_tx));
+                ops.insert(req_id, HyperStateOp(err_tx, box status, self.deadline(&inner_client)));
             }
-            hyperdex_ds_arena_destroy(arena);
 
-            Future::from_fn(move|| {
-                let err = err_rx.recv().unwrap();
-                if err.status != HYPERDEX_CLIENT_SUCCESS {
-                    Err(err)
-                } else if *status != HYPERDEX_CLIENT_SUCCESS {
+            HyperFuture::new(err_rx, move|buffers| {
+                let status: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                if *status != HYPERDEX_CLIENT_SUCCESS {
                     Err(get_client_error(*inner_client.ptr, *status))
                 } else {
                     Ok(())
@@ -1087,8 +1459,10 @@ macro_rules! make_fn_spacename_key_status(
         }
 
         pub fn $fn_name<S, K>(&mut self, space: S, key: K)
-            -> Result<(), HyperError> where S: ToCStr, K: ToHyperValue {
-            self.$async_name(space, key).into_inner()
+            -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue {
+            let space = try!(space.to_c_str()).into_bytes();
+            let key = try!(to_hyper_checked(key));
+            self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone()))
         }
         }
     );
@@ -1098,33 +1472,40 @@ macro_rules! make_fn_spacename_key_attributenames_status_attributes(
     ($fn_name: ident, $async_name: ident) => (
         impl Client {
         pub fn $async_name<S, K, A>(&mut self, space: S, key: K, attrs: Vec<A>)
-            -> Future<Result<HyperObject, HyperError>> where S: ToCStr, K: ToHyperValue, A: ToString {
+            -> HyperFuture<HyperObject> where S: ToCStr, K: TryToHyperValue, A: ToString {
             unsafe {
             // TODO: Is "Relaxed" good enough?
             let inner_client =
                 self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-            let arena = hyperdex_ds_arena_create();
-            let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
+            let key = match to_hyper_checked(key) {
+                Ok(key) => key,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+            let arena = Arena::new();
+            let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                Ok(x) => x,
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                },
+            };
 
             let mut status_ptr = box 0u32;
-            let mut attrs_ptr = box AttributePtr(null());
-            let mut attrs_sz_ptr = box 0u64;
+            let mut reply = box ReplyAttrs::new();
 
-            let mut c_attrs = match convert_attributenames(arena,
+            let mut c_attrs = match convert_attributenames(*arena,
                                                            attrs.into_iter().map(|attr| {
                                                                attr.to_string()
                                                            }).collect()) {
                 Ok(x) => x,
-                Err(err) => return Future::from_value(Err(HyperError {
-                    status: 0,
-                    message: err,
-                    location: String::new(),
-                })),
+                Err(err) => return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err))),
             };
 
-            let (err_tx, err_rx) = channel();
-            let space_str = space.to_c_str();
+            let (err_tx, err_rx) = reply_channel();
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
 
             {
                 let _lockhandle = inner_client.mutex.lock();
@@ -1137,69 +1518,78 @@ macro_rules! make_fn_spacename_key_attributenames_status_attributes(
                                                                c_attrs.as_mut_ptr(),
                                                                c_attrs.len() as u64,
                                                                &mut *status_ptr,
-                                                               &mut (*attrs_ptr).0, &mut *attrs_sz_ptr);
+                                                               &mut reply.attrs.0, &mut reply.attrs_sz);
                 if req_id < 0 {
-                    return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
                 }
-                ops.insert(req_id, HyperStateOp(err_tx));
+                ops.insert(req_id, HyperStateOp(err_tx, box (status_ptr, reply), self.deadline(&inner_client)));
             }
-            hyperdex_ds_arena_destroy(arena);
 
-            Future::from_fn(move|| {
-                let err = err_rx.recv().unwrap();
-                if err.status != HYPERDEX_CLIENT_SUCCESS {
-                    Err(err)
-                } else if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+            HyperFuture::new(err_rx, move|buffers| {
+                let (status_ptr, reply): (Box<Enum_hyperdex_client_returncode>, Box<ReplyAttrs>) =
+                    try!(take_buffers(buffers));
+                if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
                     Err(get_client_error(*inner_client.ptr, *status_ptr))
                 } else {
-                    let res = match build_hyperobject((*attrs_ptr).0, *attrs_sz_ptr) {
+                    match build_hyperobject(reply.attrs.0, reply.attrs_sz) {
                         Ok(obj) => {
                             Ok(obj)
                         },
                         Err(msg) => {
-                            Err(HyperError {
-                                status: HYPERDEX_CLIENT_SERVERERROR,
-                                message: msg,
-                                location: String::new(),
-                            })
+                            Err(HyperError::new(HyperErrorKind::Conversion, msg))
                         }
-                    };
-                    hyperdex_client_destroy_attrs((*attrs_ptr).0, *attrs_sz_ptr);
-                    res
+                    }
                 }
             })
             }
         }
 
         pub fn $fn_name<S, K, A>(&mut self, space: S, key: K, attrs: Vec<A>)
-            -> Result<HyperObject, HyperError> where S: ToCStr, K: ToHyperValue, A: ToString {
-            self.$async_name(space, key, attrs).into_inner()
+            -> Result<HyperObject, HyperError> where S: ToCStr, K: TryToHyperValue, A: ToString {
+            let space = try!(space.to_c_str()).into_bytes();
+            let key = try!(to_hyper_checked(key));
+            let attrs: Vec<String> = attrs.iter().map(|a| a.to_string()).collect();
+            self.retry(true, |client| client.$async_name(space.clone(), key.clone(), attrs.clone()))
         }
         }
     );
 );
 
 macro_rules! make_fn_spacename_key_attributes_status(
-    ($fn_name: ident, $async_name: ident) => (
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
         impl Client {
         pub fn $async_name<S, K>(&mut self, space: S, key: K, value: HyperObject)
-            -> Future<Result<(), HyperError>> where S: ToCStr, K: ToHyperValue { unsafe {
+            -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue { unsafe {
             let inner_client =
                 self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-            let arena = hyperdex_ds_arena_create();
+            let key = match to_hyper_checked(key) {
+                Ok(key) => key,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+            let arena = Arena::new();
 
-            let space_str = space.to_c_str();
-            let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+            let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                Ok(x) => x,
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                },
+            };
 
             let mut status_ptr = box 0u32;
 
-            let obj = match convert_hyperobject(arena, value) {
+            let obj = match convert_hyperobject(*arena, value) {
                 Ok(x) => x,
-                Err(err) => panic!(err),
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                },
             };
 
-            let (err_tx, err_rx) = channel();
+            let (err_tx, err_rx) = reply_channel();
 
             {
                 let _lockhandle = inner_client.mutex.lock();
@@ -1212,17 +1602,14 @@ macro_rules! make_fn_spacename_key_attributes_status(
                                                                obj.as_ptr(), obj.len() as u64,
                                                                &mut *status_ptr);
                 if req_id < 0 {
-                    return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
                 }
-                ops.insert(req_id, HyperStateOp(err_tx));
+                ops.insert(req_id, HyperStateOp(err_tx, box status_ptr, self.deadline(&inner_client)));
             }
 
-            hyperdex_ds_arena_destroy(arena);
-            Future::from_fn(move|| {
-                let err = err_rx.recv().unwrap();
-                if err.status != HYPERDEX_CLIENT_SUCCESS {
-                    Err(err)
-                } else if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+            HyperFuture::new(err_rx, move|buffers| {
+                let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
                     Err(get_client_error(*inner_client.ptr, *status_ptr))
                 } else {
                     Ok(())
@@ -1231,33 +1618,49 @@ macro_rules! make_fn_spacename_key_attributes_status(
         }}
 
         pub fn $fn_name<S, K>(&mut self, space: S, key: K, value: HyperObject)
-            -> Result<(), HyperError> where S: ToCStr, K: ToHyperValue {
-            self.$async_name(space, key, value).into_inner()
+            -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue {
+            let space = try!(space.to_c_str()).into_bytes();
+            let key = try!(to_hyper_checked(key));
+            self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), value.clone()))
         }
         }
     );
 );
 
 macro_rules! make_fn_spacename_key_mapattributes_status(
-    ($fn_name: ident, $async_name: ident) => (
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
         impl Client {
             pub fn $async_name<S, K>(&mut self, space: S, key: K, mapattrs: Vec<HyperMapAttribute>)
-                -> Future<Result<(), HyperError>> where S: ToCStr, K: ToHyperValue { unsafe {
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue { unsafe {
                 let inner_client =
                     self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-                let arena = hyperdex_ds_arena_create();
-                let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
-                let space_str = space.to_c_str();
+                let key = match to_hyper_checked(key) {
+                    Ok(key) => key,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+                let arena = Arena::new();
+                let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                };
+                let space_str = match space.to_c_str() {
+                    Ok(space_str) => space_str,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
 
                 let mut status_ptr = box 0u32;
 
-                let c_mapattrs = match convert_map_attributes(arena, mapattrs) {
+                let c_mapattrs = match convert_map_attributes(*arena, mapattrs) {
                     Ok(x) => x,
-                    Err(err) => panic!(err),
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
                 };
 
-                let (err_tx, err_rx) = channel();
+                let (err_tx, err_rx) = reply_channel();
 
                 {
                     let _lockhandle = inner_client.mutex.lock();
@@ -1270,17 +1673,14 @@ macro_rules! make_fn_spacename_key_mapattributes_status(
                                                 c_mapattrs.as_ptr(), c_mapattrs.len() as u64,
                                                 &mut *status_ptr);
                     if req_id < 0 {
-                        return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                        return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
                     }
-                    ops.insert(req_id, HyperStateOp(err_tx));
+                    ops.insert(req_id, HyperStateOp(err_tx, box status_ptr, self.deadline(&inner_client)));
                 }
 
-                hyperdex_ds_arena_destroy(arena);
-                Future::from_fn(move|| {
-                    let err = err_rx.recv().unwrap();
-                    if err.status != HYPERDEX_CLIENT_SUCCESS {
-                        Err(err)
-                    } else if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                HyperFuture::new(err_rx, move|buffers| {
+                    let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                    if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
                         Err(get_client_error(*inner_client.ptr, *status_ptr))
                     } else {
                         Ok(())
@@ -1289,45 +1689,130 @@ macro_rules! make_fn_spacename_key_mapattributes_status(
             }
 
             pub fn $fn_name<S, K>(&mut self, space: S, key: K, mapattrs: Vec<HyperMapAttribute>)
-                -> Result<(), HyperError> where S: ToCStr, K: ToHyperValue {
-                self.$async_name(space, key, mapattrs).into_inner()
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), mapattrs.clone()))
+            }
+        }
+    )
+);
+
+macro_rules! make_fn_spacename_key_predicates_status(
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
+        impl Client {
+            pub fn $async_name<S, K>(&mut self, space: S, key: K, checks: Vec<HyperPredicate>)
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue { unsafe {
+                    let inner_client =
+                        self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
+
+                    let (res_tx, res_rx) = reply_channel();
+
+                    let key = match to_hyper_checked(key) {
+                        Ok(key) => key,
+                        Err(err) => return HyperFuture::from_value(Err(err)),
+                    };
+                    let arena = Arena::new();
+                    let c_checks = match convert_predicates(*arena, checks) {
+                        Ok(x) => x,
+                        Err(err) => {
+                            return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
+                        },
+                    };
+
+                    let mut status_ptr = box 0u32;
+
+                    let space_str = match space.to_c_str() {
+                        Ok(space_str) => space_str,
+                        Err(err) => return HyperFuture::from_value(Err(err)),
+                    };
+                    let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                        Ok(x) => x,
+                        Err(err) => {
+                            return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                        },
+                    };
+
+                    {
+                        let _lockhandle = inner_client.mutex.lock();
+                        let mut ops_mutex = inner_client.ops.clone();
+                        let mut ops = &mut*ops_mutex.lock().unwrap();
+                        let req_id =
+                            concat_idents!(hyperdex_client_, $fn_name)(
+                                *inner_client.ptr,
+                                space_str.as_ptr() as *const i8,
+                                key_str, key_sz,
+                                c_checks.as_ptr(),
+                                c_checks.len() as u64,
+                                &mut *status_ptr);
+                        if req_id < 0 {
+                            return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
+                        }
+                        ops.insert(req_id, HyperStateOp(res_tx, box status_ptr, self.deadline(&inner_client)));
+                    }
+                    HyperFuture::new(res_rx, move|buffers| {
+                        let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                        if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                            Err(get_client_error(*inner_client.ptr, *status_ptr))
+                        } else {
+                            Ok(())
+                        }
+                    })
+                }
+            }
+
+            pub fn $fn_name<S, K>(&mut self, space: S, key: K, checks: Vec<HyperPredicate>)
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), checks.clone()))
             }
         }
     )
 );
 
 macro_rules! make_fn_spacename_key_predicates_attributes_status(
-    ($fn_name: ident, $async_name: ident) => (
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
         impl Client {
             pub fn $async_name<S, K>(&mut self, space: S, key: K, checks: Vec<HyperPredicate>, value: HyperObject)
-                -> Future<Result<(), HyperError>> where S: ToCStr, K: ToHyperValue { unsafe {
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue { unsafe {
                     let inner_client =
                         self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-                    let (res_tx, res_rx) = channel();
+                    let (res_tx, res_rx) = reply_channel();
 
-                    let arena = hyperdex_ds_arena_create();
-                    let c_checks = match convert_predicates(arena, checks) {
+                    let key = match to_hyper_checked(key) {
+                        Ok(key) => key,
+                        Err(err) => return HyperFuture::from_value(Err(err)),
+                    };
+                    let arena = Arena::new();
+                    let c_checks = match convert_predicates(*arena, checks) {
                         Ok(x) => x,
                         Err(err) => {
-                            return Future::from_value(Err(HyperError {
-                                status: 0,
-                                message: err,
-                                location: String::new(),
-                            }));
+                            return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
                         },
                     };
 
-                    let obj = match convert_hyperobject(arena, value) {
+                    let obj = match convert_hyperobject(*arena, value) {
                         Ok(x) => x,
-                        Err(err) => panic!(err),
+                        Err(err) => {
+                            return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                        },
                     };
 
 
                     let mut status_ptr = box 0u32;
 
-                    let space_str = space.to_c_str();
-                    let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
+                    let space_str = match space.to_c_str() {
+                        Ok(space_str) => space_str,
+                        Err(err) => return HyperFuture::from_value(Err(err)),
+                    };
+                    let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                        Ok(x) => x,
+                        Err(err) => {
+                            return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                        },
+                    };
 
                     {
                         let _lockhandle = inner_client.mutex.lock();
@@ -1344,16 +1829,13 @@ macro_rules! make_fn_spacename_key_predicates_attributes_status(
                                 obj.len() as u64,
                                 &mut *status_ptr);
                         if req_id < 0 {
-                            return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                            return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
                         }
-                        ops.insert(req_id, HyperStateOp(res_tx));
+                        ops.insert(req_id, HyperStateOp(res_tx, box status_ptr, self.deadline(&inner_client)));
                     }
-                    hyperdex_ds_arena_destroy(arena);
-                    Future::from_fn(move|| {
-                        let err = res_rx.recv().unwrap();
-                        if err.status != HYPERDEX_CLIENT_SUCCESS {
-                            Err(err)
-                        } else if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                    HyperFuture::new(res_rx, move|buffers| {
+                        let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                        if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
                             Err(get_client_error(*inner_client.ptr, *status_ptr))
                         } else {
                             Ok(())
@@ -1363,50 +1845,56 @@ macro_rules! make_fn_spacename_key_predicates_attributes_status(
             }
 
             pub fn $fn_name<S, K>(&mut self, space: S, key: K, checks: Vec<HyperPredicate>, value: HyperObject)
-                -> Result<(), HyperError> where S: ToCStr, K: ToHyperValue {
-                self.$async_name(space, key, checks, value).into_inner()
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), checks.clone(), value.clone()))
             }
         }
     )
 );
 
 macro_rules! make_fn_spacename_key_predicates_mapattributes_status(
-    ($fn_name: ident, $async_name: ident) => (
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
         impl Client {
             pub fn $async_name<S, K>(&mut self, space: S, key: K,
                                      checks: Vec<HyperPredicate>, mapattrs: Vec<HyperMapAttribute>)
-                -> Future<Result<(), HyperError>> where S: ToCStr, K: ToHyperValue { unsafe {
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue { unsafe {
                 let inner_client =
                     self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-                let arena = hyperdex_ds_arena_create();
-                let (key_str, key_sz, _) = convert_type(arena, key.to_hyper()).unwrap();
-                let space_str = space.to_c_str();
+                let key = match to_hyper_checked(key) {
+                    Ok(key) => key,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+                let arena = Arena::new();
+                let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                };
+                let space_str = match space.to_c_str() {
+                    Ok(space_str) => space_str,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
 
                 let mut status_ptr = box 0u32;
 
-                let c_checks = match convert_predicates(arena, checks) {
+                let c_checks = match convert_predicates(*arena, checks) {
                     Ok(x) => x,
                     Err(err) => {
-                        return Future::from_value(Err(HyperError {
-                            status: 0,
-                            message: err,
-                            location: String::new(),
-                        }));
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
                     },
                 };
-                let c_mapattrs = match convert_map_attributes(arena, mapattrs) {
+                let c_mapattrs = match convert_map_attributes(*arena, mapattrs) {
                     Ok(x) => x,
                     Err(err) => {
-                        return Future::from_value(Err(HyperError {
-                            status: 0,
-                            message: err,
-                            location: String::new(),
-                        }));
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
                     },
                 };
 
-                let (err_tx, err_rx) = channel();
+                let (err_tx, err_rx) = reply_channel();
 
                 {
                     let _lockhandle = inner_client.mutex.lock();
@@ -1420,17 +1908,14 @@ macro_rules! make_fn_spacename_key_predicates_mapattributes_status(
                                                 c_mapattrs.as_ptr(), c_mapattrs.len() as u64,
                                                 &mut *status_ptr);
                     if req_id < 0 {
-                        return Future::from_value(Err(get_client_error(*inner_client.ptr, 0)));
+                        return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
                     }
-                    ops.insert(req_id, HyperStateOp(err_tx));
+                    ops.insert(req_id, HyperStateOp(err_tx, box status_ptr, self.deadline(&inner_client)));
                 }
 
-                hyperdex_ds_arena_destroy(arena);
-                Future::from_fn(move|| {
-                    let err = err_rx.recv().unwrap();
-                    if err.status != HYPERDEX_CLIENT_SUCCESS {
-                        Err(err)
-                    } else if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                HyperFuture::new(err_rx, move|buffers| {
+                    let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                    if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
                         Err(get_client_error(*inner_client.ptr, *status_ptr))
                     } else {
                         Ok(())
@@ -1440,19 +1925,232 @@ macro_rules! make_fn_spacename_key_predicates_mapattributes_status(
 
             pub fn $fn_name<S, K>(&mut self, space: S, key: K,
                                   checks: Vec<HyperPredicate>, mapattrs: Vec<HyperMapAttribute>)
-                -> Result<(), HyperError> where S: ToCStr, K: ToHyperValue {
-                self.$async_name(space, key, checks, mapattrs).into_inner()
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), checks.clone(), mapattrs.clone()))
             }
         }
     )
 );
 
+macro_rules! make_fn_spacename_key_mapkeys_status(
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
+        impl Client {
+            pub fn $async_name<S, K, A, T>(&mut self, space: S, key: K, attr: A, keys: Vec<T>)
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue, A: ToString, T: TryToHyperValue { unsafe {
+                let inner_client =
+                    self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
+
+                let key = match to_hyper_checked(key) {
+                    Ok(key) => key,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+                let keys = match keys.into_iter().map(to_hyper_checked).collect::<Result<Vec<_>, _>>() {
+                    Ok(keys) => keys,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+                let arena = Arena::new();
+                let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                };
+                let space_str = match space.to_c_str() {
+                    Ok(space_str) => space_str,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+
+                let mut status_ptr = box 0u32;
+
+                let c_attrs = match convert_map_keys(*arena, attr.to_string(),
+                                                     keys) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                };
+
+                let (err_tx, err_rx) = reply_channel();
+
+                {
+                    let _lockhandle = inner_client.mutex.lock();
+                    let mut ops_mutex = inner_client.ops.clone();
+                    let mut ops = &mut*ops_mutex.lock().unwrap();
+                    let req_id =
+                        concat_idents!(hyperdex_client_, $fn_name)(*inner_client.ptr,
+                                                space_str.as_ptr() as *const i8,
+                                                key_str, key_sz,
+                                                c_attrs.as_ptr(), c_attrs.len() as u64,
+                                                &mut *status_ptr);
+                    if req_id < 0 {
+                        return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
+                    }
+                    ops.insert(req_id, HyperStateOp(err_tx, box status_ptr, self.deadline(&inner_client)));
+                }
+
+                HyperFuture::new(err_rx, move|buffers| {
+                    let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                    if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                        Err(get_client_error(*inner_client.ptr, *status_ptr))
+                    } else {
+                        Ok(())
+                    }
+                })}
+            }
+
+            pub fn $fn_name<S, K, A, T>(&mut self, space: S, key: K, attr: A, keys: Vec<T>)
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue, A: ToString, T: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                let attr = attr.to_string();
+                let keys = try!(keys.into_iter().map(to_hyper_checked).collect::<Result<Vec<_>, _>>());
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), attr.clone(), keys.clone()))
+            }
+        }
+    )
+);
+
+macro_rules! make_fn_spacename_key_predicates_mapkeys_status(
+    ($fn_name: ident, $async_name: ident, $idempotent: expr) => (
+        impl Client {
+            pub fn $async_name<S, K, A, T>(&mut self, space: S, key: K,
+                                           checks: Vec<HyperPredicate>, attr: A, keys: Vec<T>)
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue, A: ToString, T: TryToHyperValue { unsafe {
+                let inner_client =
+                    self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
+
+                let key = match to_hyper_checked(key) {
+                    Ok(key) => key,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+                let keys = match keys.into_iter().map(to_hyper_checked).collect::<Result<Vec<_>, _>>() {
+                    Ok(keys) => keys,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+                let arena = Arena::new();
+                let (key_str, key_sz, _) = match convert_type(*arena, key) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                };
+                let space_str = match space.to_c_str() {
+                    Ok(space_str) => space_str,
+                    Err(err) => return HyperFuture::from_value(Err(err)),
+                };
+
+                let mut status_ptr = box 0u32;
+
+                let c_checks = match convert_predicates(*arena, checks) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
+                    },
+                };
+                let c_attrs = match convert_map_keys(*arena, attr.to_string(),
+                                                     keys) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                };
+
+                let (err_tx, err_rx) = reply_channel();
+
+                {
+                    let _lockhandle = inner_client.mutex.lock();
+                    let mut ops_mutex = inner_client.ops.clone();
+                    let mut ops = &mut*ops_mutex.lock().unwrap();
+                    let req_id =
+                        concat_idents!(hyperdex_client_, $fn_name)(*inner_client.ptr,
+                                                space_str.as_ptr() as *const i8,
+                                                key_str, key_sz,
+                                                c_checks.as_ptr(), c_checks.len() as u64,
+                                                c_attrs.as_ptr(), c_attrs.len() as u64,
+                                                &mut *status_ptr);
+                    if req_id < 0 {
+                        return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
+                    }
+                    ops.insert(req_id, HyperStateOp(err_tx, box status_ptr, self.deadline(&inner_client)));
+                }
+
+                HyperFuture::new(err_rx, move|buffers| {
+                    let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                    if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                        Err(get_client_error(*inner_client.ptr, *status_ptr))
+                    } else {
+                        Ok(())
+                    }
+                })}
+            }
+
+            pub fn $fn_name<S, K, A, T>(&mut self, space: S, key: K,
+                                        checks: Vec<HyperPredicate>, attr: A, keys: Vec<T>)
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue, A: ToString, T: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                let attr = attr.to_string();
+                let keys = try!(keys.into_iter().map(to_hyper_checked).collect::<Result<Vec<_>, _>>());
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), checks.clone(), attr.clone(), keys.clone()))
+            }
+        }
+    )
+);
+
+macro_rules! make_fn_spacename_key_docpath_value_status(
+    ($fn_name: ident, $async_name: ident, $async_obj_name: ident, $idempotent: expr) => (
+        impl Client {
+            pub fn $async_name<S, K, P, T>(&mut self, space: S, key: K, path: P, value: T)
+                -> HyperFuture<()> where S: ToCStr, K: TryToHyperValue, P: ToString, T: TryToHyperValue {
+                let path = path.to_string();
+                match check_doc_path(path.as_str()) {
+                    Ok(()) => (),
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
+                    },
+                }
+
+                let mut obj = HyperObject::new();
+                match obj.try_insert(path, value) {
+                    Ok(()) => (),
+                    Err(err) => {
+                        return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::Conversion, err)));
+                    },
+                }
+                self.$async_obj_name(space, key, obj)
+            }
+
+            pub fn $fn_name<S, K, P, T>(&mut self, space: S, key: K, path: P, value: T)
+                -> Result<(), HyperError> where S: ToCStr, K: TryToHyperValue, P: ToString, T: TryToHyperValue {
+                let space = try!(space.to_c_str()).into_bytes();
+                let key = try!(to_hyper_checked(key));
+                let path = path.to_string();
+                let value = try!(to_hyper_checked(value));
+                self.retry($idempotent, |client| client.$async_name(space.clone(), key.clone(), path.clone(), value.clone()))
+            }
+        }
+    )
+);
+
+/// The default longest time, in milliseconds, that an event loop blocks waiting for HyperDex.
+const DEFAULT_BLOCK_MS: u32 = 250;
+
+/// The number of search results buffered for a `SearchStream`.  As many again are queued
+/// before the client stops pulling more results from HyperDex; see `SearchStream`.
+const DEFAULT_SEARCH_BUFFER_SIZE: usize = 256;
+
 /// A HyperDex client, used for common operations like getting and putting objects.
 ///
 /// The functions implemented by this client correspond 1-to-1 to those in the C API.
 /// Please refer to [HyperDex's official documentation](http://hyperdex.org/doc/latest/CClientAPI/#chap:api:c-client) for details:
 pub struct Client {
     counter: AtomicUsize,
+    search_buffer_size: usize,
+    retry_policy: RetryPolicy,
+    timeout_ms: Option<u32>,
+    reactor: Option<LoopState>,
     shutdown_txs: Vec<Sender<()>>,
     inner_clients: Vec<InnerClient>,
 }
@@ -1460,82 +2158,551 @@ pub struct Client {
 impl Client {
 
     /// Creates a new client.
+    ///
+    /// Use a `ClientBuilder` to configure the client before it connects.
     pub fn new(coordinator: SocketAddr) -> Result<Client, String> {
-        let ip_str = format!("{}", coordinator.ip()).to_c_str();
+        ClientBuilder::new().build(coordinator)
+    }
 
+    /// Creates a new client from a connection string such as `"host1:1982,host2:1982"`.
+    ///
+    /// Coordinators may be given as hostnames or IPs.  Entries without a port use 1982.
+    pub fn from_conn_str(conn: &str) -> Result<Client, String> {
+        ClientBuilder::new().build_from_conn_str(conn)
+    }
+
+    /// Creates a client that starts no threads and is driven by an external event loop,
+    /// such as mio or tokio.
+    ///
+    /// Register `poll_fd()` with the event loop for readability and call `process_events`
+    /// whenever it is readable.  `process_events` handles every reply that is ready, so the
+    /// fd may be registered edge-triggered.  Nothing runs while the fd is idle, however many
+    /// operations are in flight.
+    ///
+    /// Replies are only processed by `process_events`, so the blocking methods, such as
+    /// `put` and `get`, fail with `HyperErrorKind::InvalidArgument`; use the `async_*`
+    /// methods instead.  Waiting on a future or iterating a `SearchStream` from the thread
+    /// that runs the event loop blocks forever if the reply has not been processed yet.
+    /// Use `pending` to tell when every operation has completed.  After starting an
+    /// operation with a timeout, call `process_events` again so that the timeout it returns
+    /// covers the new deadline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut client = Client::new_reactor(coord_addr).unwrap();
+    /// let fd = client.poll_fd().unwrap();
+    /// poll.register(&EventedFd(&fd), Token(0), EventSet::readable(), PollOpt::edge()).unwrap();
+    ///
+    /// let res = client.async_put(space_name, "derek", obj);
+    /// while client.pending() > 0 {
+    ///     let timeout = client.process_events();
+    ///     poll.poll(&mut events, timeout.map(|ms| ms as usize)).unwrap();
+    /// }
+    /// res.into_inner().unwrap();
+    /// ```
+    pub fn new_reactor(coordinator: SocketAddr) -> Result<Client, String> {
+        ClientBuilder::new().reactor(true).build(coordinator)
+    }
+
+    /// Creates a client driven by an external event loop from a connection string.  See
+    /// `new_reactor` and `from_conn_str`.
+    pub fn from_conn_str_reactor(conn: &str) -> Result<Client, String> {
+        ClientBuilder::new().reactor(true).build_from_conn_str(conn)
+    }
+
+    /// Creates the inner clients as configured by `builder`.  A reactor client has a single
+    /// inner client and no threads; otherwise every inner client has its own thread.
+    fn from_create_fn<F>(builder: &ClientBuilder, create_fn: F) -> Result<Client, String>
+        where F: Fn() -> *mut Struct_hyperdex_client {
         let (err_tx, err_rx) = channel();
 
+        let num_clients = try!(builder.num_inner_clients());
         let mut inner_clients = Vec::new();
         let mut shutdown_txs = Vec::new();
-        for _ in 0..num_cpus::get() {
-            let ptr = unsafe { hyperdex_client_create(ip_str.as_ptr(), coordinator.port()) };
+        for i in 0..num_clients {
+            let wake = if builder.reactor {
+                None
+            } else {
+                Some(Arc::new(try!(WakePipe::new())))
+            };
+            let ptr = create_fn();
             if ptr.is_null() {
                 return Err(format!("Unable to create client.  errno is: {}", errno::errno()));
             } else {
                 let ops = Arc::new(Mutex::new(HashMap::new()));
-                let (shutdown_tx, shutdown_rx) = channel();
                 let mut inner_client = InnerClient {
                     ptr: unsafe { Unique::new(ptr) },
                     ops: ops.clone(),
                     err_tx: err_tx.clone(),
                     mutex: Arc::new(Mutex::new(())),
+                    block_ms: builder.block_ms as i32,
+                    wake: wake,
                 };
-                let mut ic_clone = inner_client.clone();
-                thread::spawn(move|| {
-                    ic_clone.run_forever(shutdown_rx);
-                });
+                if !builder.reactor {
+                    let (shutdown_tx, shutdown_rx) = channel();
+                    let mut ic_clone = inner_client.clone();
+                    let mut thread_builder = thread::Builder::new();
+                    if let Some(ref name) = builder.thread_name {
+                        thread_builder = thread_builder.name(format!("{}-{}", name, i));
+                    }
+                    if let Some(size) = builder.stack_size {
+                        thread_builder = thread_builder.stack_size(size);
+                    }
+                    let spawned = thread_builder.spawn(move|| {
+                        ic_clone.run_forever(shutdown_rx);
+                    });
+                    if let Err(err) = spawned {
+                        unsafe {
+                            hyperdex_client_destroy(ptr);
+                        }
+                        return Err(format!("Unable to start client thread: {}", err));
+                    }
+                    shutdown_txs.push(shutdown_tx);
+                }
                 inner_clients.push(inner_client);
-                shutdown_txs.push(shutdown_tx);
             }
         };
 
+        let reactor_state = if builder.reactor {
+            Some(LoopState::new())
+        } else {
+            None
+        };
+
         Ok(Client {
             counter: AtomicUsize::new(0),
+            search_buffer_size: builder.search_buffer_size,
+            retry_policy: builder.retry_policy.clone(),
+            timeout_ms: builder.timeout_ms,
+            reactor: reactor_state,
             inner_clients: inner_clients,
             shutdown_txs: shutdown_txs,
         })
     }
 
+    /// The fd to register with an external event loop, or `None` if the client runs its own
+    /// threads.  See `new_reactor`.
+    pub fn poll_fd(&self) -> Option<RawFd> {
+        match self.reactor {
+            Some(_) => Some(unsafe { hyperdex_client_poll(*self.inner_clients[0].ptr) }),
+            None => None,
+        }
+    }
+
+    /// Handles every reply that HyperDex has ready, without blocking, hands queued search
+    /// results to their streams and fails the operations and searches whose timeouts have
+    /// passed.
+    ///
+    /// Returns the number of milliseconds after which the event loop should call this again
+    /// even if the fd stays idle: when the next operation or search times out, or shortly if search
+    /// results are waiting for their streams to be read.  `None` means that only the fd
+    /// needs watching.  Does nothing for a client that runs its own threads.
+    ///
+    /// While a `SearchStream` is too far behind, replies are left unread; see
+    /// `SearchStream`.  The returned timeout covers that case, so the fd need not be
+    /// readable again for them to be handled.
+    pub fn process_events(&mut self) -> Option<u64> {
+        let loop_state = match self.reactor {
+            Some(ref mut loop_state) => loop_state,
+            None => return None,
+        };
+        let inner_client = &self.inner_clients[0];
+
+        let mut queued;
+        loop {
+            let (q, paused) = inner_client.flush_searches();
+            queued = q;
+            if paused {
+                break;
+            }
+            match unsafe { inner_client.step(loop_state) } {
+                // A deferred reply was set aside, so keep draining the rest
+                Step::Handled | Step::Deferred => (),
+                Step::Idle => break,
+            }
+        }
+
+        let next_ms = inner_client.expire_ops(&mut loop_state.expired);
+        if queued {
+            Some(next_ms.map_or(FLUSH_INTERVAL_MS as u64, |ms| cmp::min(ms, FLUSH_INTERVAL_MS as u64)))
+        } else {
+            next_ms
+        }
+    }
+
+    /// The number of operations and searches that have not completed yet.
+    pub fn pending(&self) -> usize {
+        self.inner_clients.iter().fold(0, |n, ic| n + ic.ops.lock().unwrap().len())
+    }
+
+    /// Sets how the blocking methods retry operations that fail with a transient error.  By
+    /// default they follow `RetryPolicy::new()`, which does not retry operations that are not
+    /// idempotent; use `RetryPolicy::never()` to turn retries off.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Sets the timeout, in milliseconds, of every operation that does not set its own with
+    /// `with_timeout`.  `None`, the default, waits forever.
+    ///
+    /// An operation that times out fails with `HyperErrorKind::DeadlineExceeded`, and
+    /// HyperDex's reply is dropped if it arrives later.  The timeout applies to each attempt
+    /// of an operation that is retried.  A search must finish within the timeout, or its
+    /// stream ends with a `DeadlineExceeded` error.
+    ///
+    /// HyperDex may still write its reply into the buffers of an operation that timed out,
+    /// so they are kept until the reply arrives.  If none has arrived after
+    /// `EXPIRED_OP_TTL_MS`, the buffers are leaked rather than freed under HyperDex.  An
+    /// operation that never gets a reply, for example because the cluster is down, leaks
+    /// them in the same way.
+    pub fn set_default_timeout(&mut self, timeout_ms: Option<u32>) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    pub fn default_timeout(&self) -> Option<u32> {
+        self.timeout_ms
+    }
+
+    /// Returns the client with a different timeout for the calls made through it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let obj = client.with_timeout(100).get(space_name, "derek");
+    /// ```
+    pub fn with_timeout(&mut self, timeout_ms: u32) -> WithTimeout {
+        let saved = self.timeout_ms;
+        self.timeout_ms = Some(timeout_ms);
+        WithTimeout {
+            client: self,
+            saved: saved,
+        }
+    }
+
+    /// The deadline, in the nanoseconds of `time::precise_time_ns`, of an operation started now
+    /// on `inner_client`.  Its event loop is woken so that it does not wait past the deadline.
+    fn deadline(&self, inner_client: &InnerClient) -> Option<u64> {
+        let deadline = self.timeout_ms.map(|ms| time::precise_time_ns() + ms as u64 * 1_000_000);
+        if deadline.is_some() {
+            if let Some(ref wake) = inner_client.wake {
+                wake.wake();
+            }
+        }
+        deadline
+    }
+
+    /// Runs an operation until it succeeds or fails with an error that the retry policy
+    /// does not retry.
+    fn retry<T, F>(&mut self, idempotent: bool, mut op: F) -> Result<T, HyperError>
+        where F: FnMut(&mut Client) -> HyperFuture<T> {
+        if self.reactor.is_some() {
+            // Nothing would process the reply while this thread waits for it
+            return Err(HyperError::new(HyperErrorKind::InvalidArgument,
+                                       "blocking methods are not available on a reactor client"));
+        }
+
+        let policy = self.retry_policy.clone();
+        let mut attempt = 1;
+        loop {
+            match op(self).into_inner() {
+                Err(ref err) if policy.should_retry(err, attempt, idempotent) => {
+                    thread::sleep_ms(policy.jittered_backoff_ms(attempt));
+                    attempt += 1;
+                },
+                res => return res,
+            }
+        }
+    }
+
+    /// Searches for objects that match the given predicates.
+    ///
+    /// Results arrive through a `SearchStream`; see its documentation for buffering and
+    /// cancellation.
     pub fn search<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
-        -> Receiver<Result<HyperObject, HyperError>> where S: ToCStr { unsafe {
+        -> SearchStream where S: ToCStr {
+        let space_str = match space.to_c_str() {
+            Ok(space_str) => space_str,
+            Err(err) => return SearchStream::from_error(err),
+        };
+        self.start_search(checks, |ptr, c_checks, c_checks_sz, status, attrs, attrs_sz| unsafe {
+            hyperdex_client_search(ptr,
+                                   space_str.as_ptr() as *const i8,
+                                   c_checks, c_checks_sz,
+                                   status, attrs, attrs_sz)
+        })
+    }
+
+    /// Searches for objects that match the given predicates, sorted by `sort_by`.
+    ///
+    /// At most `limit` objects are returned.  If `descending` is true, objects with the largest
+    /// values of `sort_by` are returned first.
+    pub fn sorted_search<S, A>(&mut self, space: S, checks: Vec<HyperPredicate>,
+                               sort_by: A, limit: u64, descending: bool)
+        -> SearchStream where S: ToCStr, A: ToCStr {
+        let space_str = match space.to_c_str() {
+            Ok(space_str) => space_str,
+            Err(err) => return SearchStream::from_error(err),
+        };
+        let sort_by_str = match sort_by.to_c_str() {
+            Ok(sort_by_str) => sort_by_str,
+            Err(err) => return SearchStream::from_error(err),
+        };
+        self.start_search(checks, |ptr, c_checks, c_checks_sz, status, attrs, attrs_sz| unsafe {
+            hyperdex_client_sorted_search(ptr,
+                                          space_str.as_ptr() as *const i8,
+                                          c_checks, c_checks_sz,
+                                          sort_by_str.as_ptr() as *const i8,
+                                          limit,
+                                          if descending { 1 } else { 0 },
+                                          status, attrs, attrs_sz)
+        })
+    }
+
+    /// Walks the objects that match the given predicates in pages of `page_size`, ordered by
+    /// `sort_by` and then by the key, which is named by `key_attr`.  See `Paginator` for
+    /// details.
+    pub fn paginate<S, A, K>(&mut self, space: S, checks: Vec<HyperPredicate>,
+                             sort_by: A, key_attr: K, page_size: u64, descending: bool)
+        -> Paginator where S: ToString, A: ToString, K: ToString {
+        Paginator::new(self, space.to_string(), checks, sort_by.to_string(), key_attr.to_string(),
+                       page_size, descending)
+    }
+
+    /// Searches for objects that match a boolean combination of predicates.  Results are
+    /// de-duplicated by the key, which is named by `key_attr`.  See `QueryExpr` for how the
+    /// expression is evaluated.
+    ///
+    /// The expression is rewritten into one search per conjunction of its disjunctive normal
+    /// form, which can grow exponentially with nested `or`s under an `and`.  An expression
+    /// that expands to more than `MAX_CONJUNCTS` (64) conjunctions is rejected, and the
+    /// stream yields a single `InvalidArgument` error.  A negated predicate that has no
+    /// opposite comparison costs one more search in each conjunction it appears in.
+    ///
+    /// The keys of all the objects returned are kept until the stream is dropped.
+    pub fn search_expr<S, K>(&mut self, space: S, key_attr: K, expr: QueryExpr)
+        -> QueryStream where S: ToString, K: ToString {
+        QueryStream::new(self, space.to_string(), key_attr.to_string(), expr)
+    }
+
+    /// Counts the objects that match the given predicates.
+    pub fn count<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> Result<u64, HyperError> where S: ToCStr {
+        let space = try!(space.to_c_str()).into_bytes();
+        self.retry(true, |client| client.async_count(space.clone(), checks.clone()))
+    }
+
+    pub fn async_count<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> HyperFuture<u64> where S: ToCStr { unsafe {
+            let inner_client =
+                self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
+
+            let arena = Arena::new();
+            let c_checks = match convert_predicates(*arena, checks) {
+                Ok(x) => x,
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
+                },
+            };
+
+            let mut status_ptr = box 0u32;
+            let mut count_ptr = box 0u64;
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+
+            let (err_tx, err_rx) = reply_channel();
+
+            {
+                let _lockhandle = inner_client.mutex.lock();
+                let mut ops_mutex = inner_client.ops.clone();
+                let mut ops = &mut*ops_mutex.lock().unwrap();
+                let req_id = hyperdex_client_count(*inner_client.ptr,
+                                                   space_str.as_ptr() as *const i8,
+                                                   c_checks.as_ptr(),
+                                                   c_checks.len() as u64,
+                                                   &mut *status_ptr,
+                                                   &mut *count_ptr);
+                if req_id < 0 {
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
+                }
+                ops.insert(req_id, HyperStateOp(err_tx, box (status_ptr, count_ptr), self.deadline(&inner_client)));
+            }
+
+            HyperFuture::new(err_rx, move|buffers| {
+                let (status_ptr, count_ptr): (Box<Enum_hyperdex_client_returncode>, Box<u64>) =
+                    try!(take_buffers(buffers));
+                if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                    Err(get_client_error(*inner_client.ptr, *status_ptr))
+                } else {
+                    Ok(*count_ptr)
+                }
+            })
+        }
+    }
+
+    /// Deletes all objects that match the given predicates.
+    pub fn group_del<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> Result<(), HyperError> where S: ToCStr {
+        let space = try!(space.to_c_str()).into_bytes();
+        self.retry(true, |client| client.async_group_del(space.clone(), checks.clone()))
+    }
+
+    /// Returns the number of objects that `group_del` would delete, without deleting anything.
+    pub fn group_del_dry_run<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> Result<u64, HyperError> where S: ToCStr {
+        self.count(space, checks)
+    }
+
+    pub fn async_group_del<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> HyperFuture<()> where S: ToCStr { unsafe {
             let inner_client =
                 self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
 
-            let (res_tx, res_rx) = channel();
+            let arena = Arena::new();
+            let c_checks = match convert_predicates(*arena, checks) {
+                Ok(x) => x,
+                Err(err) => {
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
+                },
+            };
+
+            let mut status_ptr = box 0u32;
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
 
-            let arena = hyperdex_ds_arena_create();
-            let c_checks = match convert_predicates(arena, checks) {
+            let (err_tx, err_rx) = reply_channel();
+
+            {
+                let _lockhandle = inner_client.mutex.lock();
+                let mut ops_mutex = inner_client.ops.clone();
+                let mut ops = &mut*ops_mutex.lock().unwrap();
+                let req_id = hyperdex_client_group_del(*inner_client.ptr,
+                                                       space_str.as_ptr() as *const i8,
+                                                       c_checks.as_ptr(),
+                                                       c_checks.len() as u64,
+                                                       &mut *status_ptr);
+                if req_id < 0 {
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
+                }
+                ops.insert(req_id, HyperStateOp(err_tx, box status_ptr, self.deadline(&inner_client)));
+            }
+
+            HyperFuture::new(err_rx, move|buffers| {
+                let status_ptr: Box<Enum_hyperdex_client_returncode> = try!(take_buffers(buffers));
+                if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                    Err(get_client_error(*inner_client.ptr, *status_ptr))
+                } else {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    /// Describes how the servers would execute a search with the given predicates.
+    pub fn describe_search<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> Result<SearchDescription, HyperError> where S: ToCStr {
+        let space = try!(space.to_c_str()).into_bytes();
+        self.retry(true, |client| client.async_describe_search(space.clone(), checks.clone()))
+    }
+
+    pub fn async_describe_search<S>(&mut self, space: S, checks: Vec<HyperPredicate>)
+        -> HyperFuture<SearchDescription> where S: ToCStr { unsafe {
+            let inner_client =
+                self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
+
+            let arena = Arena::new();
+            let c_checks = match convert_predicates(*arena, checks) {
                 Ok(x) => x,
                 Err(err) => {
-                    res_tx.send(Err(HyperError {
-                        status: 0,
-                        message: err,
-                        location: String::new(),
-                    }));
-                    return res_rx;
+                    return HyperFuture::from_value(Err(HyperError::new(HyperErrorKind::InvalidArgument, err)));
+                },
+            };
+
+            let mut status_ptr = box 0u32;
+            let mut desc_ptr = box ReplyString(CStrPtr(null()));
+            let space_str = match space.to_c_str() {
+                Ok(space_str) => space_str,
+                Err(err) => return HyperFuture::from_value(Err(err)),
+            };
+
+            let (err_tx, err_rx) = reply_channel();
+
+            {
+                let _lockhandle = inner_client.mutex.lock();
+                let mut ops_mutex = inner_client.ops.clone();
+                let mut ops = &mut*ops_mutex.lock().unwrap();
+                let req_id = hyperdex_client_search_describe(*inner_client.ptr,
+                                                             space_str.as_ptr() as *const i8,
+                                                             c_checks.as_ptr(),
+                                                             c_checks.len() as u64,
+                                                             &mut *status_ptr,
+                                                             &mut (desc_ptr.0).0);
+                if req_id < 0 {
+                    return HyperFuture::from_value(Err(get_client_error(*inner_client.ptr, *status_ptr)));
+                }
+                ops.insert(req_id, HyperStateOp(err_tx, box (status_ptr, desc_ptr), self.deadline(&inner_client)));
+            }
+
+            HyperFuture::new(err_rx, move|buffers| {
+                let (status_ptr, desc_ptr): (Box<Enum_hyperdex_client_returncode>, Box<ReplyString>) =
+                    try!(take_buffers(buffers));
+                if *status_ptr != HYPERDEX_CLIENT_SUCCESS {
+                    Err(get_client_error(*inner_client.ptr, *status_ptr))
+                } else if (desc_ptr.0).0.is_null() {
+                    Ok(SearchDescription::parse(""))
+                } else {
+                    // ReplyString frees the description, which HyperDex allocated for us
+                    Ok(SearchDescription::parse(to_string((desc_ptr.0).0).as_str()))
+                }
+            })
+        }
+    }
+
+    fn start_search<F>(&mut self, checks: Vec<HyperPredicate>, search_fn: F)
+        -> SearchStream
+        where F: FnOnce(*mut Struct_hyperdex_client,
+                        *const Struct_hyperdex_client_attribute_check, size_t,
+                        *mut Enum_hyperdex_client_returncode,
+                        *mut *const Struct_hyperdex_client_attribute,
+                        *mut size_t) -> int64_t { unsafe {
+            let inner_client =
+                self.inner_clients[self.counter.fetch_add(1, Ordering::Relaxed) as usize % self.inner_clients.len()].clone();
+
+            let (res_tx, res_rx) = event_channel(self.search_buffer_size);
+            let cancelled = Arc::new(AtomicBool::new(false));
+
+            let arena = Arena::new();
+            let c_checks = match convert_predicates(*arena, checks) {
+                Ok(x) => x,
+                Err(err) => {
+                    return SearchStream::from_error(HyperError::new(HyperErrorKind::InvalidArgument, err));
                 },
             };
 
             let mut status_ptr = box 0u32;
             let mut attrs_ptr = box AttributePtr(null());
             let mut attrs_sz_ptr = box 0u64;
-            let space_str = space.to_c_str();
 
             let _lockhandle = inner_client.mutex.lock();
             let mut ops_mutex = inner_client.ops.clone();
             {
                 let mut ops = &mut*ops_mutex.lock().unwrap();
-                let req_id =
-                    hyperdex_client_search(*inner_client.ptr,
-                                           space_str.as_ptr() as *const i8,
-                                           c_checks.as_ptr(),
-                                           c_checks.len() as u64,
-                                           &mut *status_ptr,
-                                           &mut (*attrs_ptr).0,
-                                           &mut *attrs_sz_ptr);
+                let req_id = search_fn(*inner_client.ptr,
+                                       c_checks.as_ptr(),
+                                       c_checks.len() as u64,
+                                       &mut *status_ptr,
+                                       &mut (*attrs_ptr).0,
+                                       &mut *attrs_sz_ptr);
                 if req_id < 0 {
-                    res_tx.send(Err(get_client_error(*inner_client.ptr, 0)));
-                    return res_rx;
+                    return SearchStream::from_error(get_client_error(*inner_client.ptr, *status_ptr));
                 }
 
                 let mut state = SearchState {
@@ -1543,74 +2710,270 @@ impl Client {
                     attrs: attrs_ptr,
                     attrs_sz: attrs_sz_ptr,
                     res_tx: res_tx,
+                    cancelled: cancelled.clone(),
+                    overflow: VecDeque::new(),
+                    overflow_limit: self.search_buffer_size,
+                    done: false,
+                    deadline: self.deadline(&inner_client),
+                    expired: false,
                 };
 
                 ops.insert(req_id, HyperStateSearch(state));
             }
-            hyperdex_ds_arena_destroy(arena);
-            return res_rx;
+            return SearchStream::new(res_rx, cancelled);
         }
     }
+}
 
-    // pub fn new_from_conn_str(conn: String) -> Result<Client, String> {
-        // let conn_str = conn.to_c_str().as_ptr();
-        // let ptr = unsafe { hyperdex_client_create_conn_str(conn_str) };
-        // if ptr.is_null() {
-            // Err(format!("Unable to create client.  errno is: {}", errno()))
-        // } else {
-            // unsafe {
-                // Ok(Client {
-                    // ptr: ptr
-                // })
-            // }
-        // }
-    // }
+/// Configures a `Client` before it connects.
+///
+/// By default, a client has one inner `hyperdex_client`, each with its own coordinator
+/// connection and event loop thread, per CPU.  Small processes on large hosts may want far
+/// fewer.
+///
+/// # Examples
+///
+/// ```
+/// let client = ClientBuilder::new()
+///     .inner_clients(2)
+///     .thread_name("hyperdex")
+///     .default_timeout(Some(1000))
+///     .retry_policy(RetryPolicy::new().max_attempts(5))
+///     .build_from_conn_str("127.0.0.1:1982")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    inner_clients: Option<usize>,
+    thread_name: Option<String>,
+    stack_size: Option<usize>,
+    block_ms: u32,
+    timeout_ms: Option<u32>,
+    retry_policy: RetryPolicy,
+    search_buffer_size: usize,
+    reactor: bool,
+}
+
+impl ClientBuilder {
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            inner_clients: None,
+            thread_name: None,
+            stack_size: None,
+            block_ms: DEFAULT_BLOCK_MS,
+            timeout_ms: None,
+            retry_policy: RetryPolicy::new(),
+            search_buffer_size: DEFAULT_SEARCH_BUFFER_SIZE,
+            reactor: false,
+        }
+    }
+
+    /// The number of inner clients, each with its own coordinator connection and thread.
+    /// Defaults to the number of CPUs.
+    pub fn inner_clients(mut self, num: usize) -> ClientBuilder {
+        self.inner_clients = Some(num);
+        self
+    }
+
+    /// Names the event loop threads `"<name>-0"`, `"<name>-1"` and so on.
+    pub fn thread_name<S>(mut self, name: S) -> ClientBuilder where S: ToString {
+        self.thread_name = Some(name.to_string());
+        self
+    }
+
+    /// The stack size, in bytes, of the event loop threads.
+    pub fn stack_size(mut self, size: usize) -> ClientBuilder {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// The longest time, in milliseconds, that an event loop thread blocks waiting for
+    /// HyperDex before it checks whether the client has been dropped.  Defaults to 250.
+    pub fn block_interval(mut self, ms: u32) -> ClientBuilder {
+        self.block_ms = ms;
+        self
+    }
+
+    /// See `Client::set_default_timeout`.
+    pub fn default_timeout(mut self, timeout_ms: Option<u32>) -> ClientBuilder {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// See `Client::set_retry_policy`.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ClientBuilder {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// The number of search results buffered for each `SearchStream`.  Defaults to 256.
+    pub fn search_buffer_size(mut self, size: usize) -> ClientBuilder {
+        self.search_buffer_size = size;
+        self
+    }
+
+    /// Whether the client is driven by an external event loop instead of its own threads.
+    /// See `Client::new_reactor`.
+    pub fn reactor(mut self, reactor: bool) -> ClientBuilder {
+        self.reactor = reactor;
+        self
+    }
+
+    pub fn build(&self, coordinator: SocketAddr) -> Result<Client, String> {
+        let ip_str = match format!("{}", coordinator.ip()).to_c_str() {
+            Ok(ip_str) => ip_str,
+            Err(err) => return Err(err.message),
+        };
+        Client::from_create_fn(self, || unsafe {
+            hyperdex_client_create(ip_str.as_ptr(), coordinator.port())
+        })
+    }
+
+    /// Builds a client from a connection string.  See `Client::from_conn_str`.
+    pub fn build_from_conn_str(&self, conn: &str) -> Result<Client, String> {
+        let coordinators = try!(parse_conn_str(conn));
+        let conn_str = coordinators.iter().map(|&(ref host, port)| {
+            if host.contains(":") {
+                format!("[{}]:{}", host, port)
+            } else {
+                format!("{}:{}", host, port)
+            }
+        }).collect::<Vec<String>>().connect(",");
+        let conn_str = match conn_str.to_c_str() {
+            Ok(conn_str) => conn_str,
+            Err(err) => return Err(err.message),
+        };
+        Client::from_create_fn(self, || unsafe {
+            hyperdex_client_create_conn_str(conn_str.as_ptr())
+        })
+    }
+
+    /// The number of inner clients to create, after checking the configuration.
+    fn num_inner_clients(&self) -> Result<usize, String> {
+        if self.block_ms == 0 || self.block_ms > i32::MAX as u32 {
+            return Err(format!("Invalid block interval: {}ms", self.block_ms));
+        }
+        if self.search_buffer_size == 0 {
+            return Err("The search buffer size must be positive".to_string());
+        }
+        match (self.inner_clients, self.reactor) {
+            (Some(0), _) => Err("A client needs at least one inner client".to_string()),
+            (Some(1), true) | (None, true) => Ok(1),
+            (Some(_), true) => Err("A reactor client has exactly one inner client".to_string()),
+            (Some(num), false) => Ok(num),
+            (None, false) => Ok(num_cpus::get()),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Threaded inner clients destroy themselves once their shutdown channel is closed
+        if self.reactor.is_some() {
+            for inner_client in self.inner_clients.iter() {
+                unsafe {
+                    hyperdex_client_destroy(*inner_client.ptr);
+                }
+            }
+        }
+    }
+}
+
+/// A `Client` whose operations use the timeout given to `Client::with_timeout`.
+///
+/// The client's default timeout is restored when this is dropped.
+pub struct WithTimeout<'a> {
+    client: &'a mut Client,
+    saved: Option<u32>,
+}
+
+impl<'a> Deref for WithTimeout<'a> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client
+    }
+}
+
+impl<'a> DerefMut for WithTimeout<'a> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client
+    }
+}
+
+impl<'a> Drop for WithTimeout<'a> {
+    fn drop(&mut self) {
+        self.client.timeout_ms = self.saved;
+    }
 }
 
-make_fn_spacename_key_status!(del, async_del);
+make_fn_spacename_key_status!(del, async_del, false);
+make_fn_spacename_key_predicates_status!(cond_del, async_cond_del, false);
 make_fn_spacename_key_status_attributes!(get, async_get);
 
 make_fn_spacename_key_attributenames_status_attributes!(get_partial, async_get_partial);
 
-make_fn_spacename_key_attributes_status!(put, async_put);
-make_fn_spacename_key_predicates_attributes_status!(cond_put, async_cond_put);
-make_fn_spacename_key_attributes_status!(put_if_not_exist, async_put_if_not_exist);
-make_fn_spacename_key_attributes_status!(atomic_add, async_atomic_add);
-make_fn_spacename_key_attributes_status!(atomic_sub, async_atomic_sub);
-make_fn_spacename_key_attributes_status!(atomic_mul, async_atomic_mul);
-make_fn_spacename_key_attributes_status!(atomic_div, async_atomic_div);
-make_fn_spacename_key_attributes_status!(atomic_mod, async_atomic_mod);
-make_fn_spacename_key_attributes_status!(atomic_and, async_atomic_and);
-make_fn_spacename_key_attributes_status!(atomic_or, async_atomic_or);
-make_fn_spacename_key_attributes_status!(atomic_xor, async_atomic_xor);
-make_fn_spacename_key_attributes_status!(string_prepend, async_string_prepend);
-make_fn_spacename_key_attributes_status!(string_append, async_string_append);
-make_fn_spacename_key_attributes_status!(list_lpush, async_list_lpush);
-make_fn_spacename_key_attributes_status!(list_rpush, async_list_rpush);
-make_fn_spacename_key_attributes_status!(set_add, async_set_add);
-make_fn_spacename_key_attributes_status!(set_remove, async_set_remove);
-make_fn_spacename_key_attributes_status!(set_intersect, async_set_intersect);
-make_fn_spacename_key_attributes_status!(set_union, async_set_union);
-make_fn_spacename_key_attributes_status!(map_remove, async_map_remove);
-make_fn_spacename_key_mapattributes_status!(map_add, async_map_add);
-make_fn_spacename_key_mapattributes_status!(map_atomic_add, async_map_atomic_add);
-make_fn_spacename_key_mapattributes_status!(map_atomic_sub, async_map_atomic_sub);
-make_fn_spacename_key_mapattributes_status!(map_atomic_mul, async_map_atomic_mul);
-make_fn_spacename_key_mapattributes_status!(map_atomic_div, async_map_atomic_div);
-make_fn_spacename_key_mapattributes_status!(map_atomic_mod, async_map_atomic_mod);
-make_fn_spacename_key_mapattributes_status!(map_atomic_and, async_map_atomic_and);
-make_fn_spacename_key_mapattributes_status!(map_atomic_or, async_map_atomic_or);
-make_fn_spacename_key_mapattributes_status!(map_atomic_xor, async_map_atomic_xor);
-make_fn_spacename_key_mapattributes_status!(map_string_prepend, async_map_string_prepend);
-make_fn_spacename_key_mapattributes_status!(map_string_append, async_map_string_append);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_add, async_cond_map_add);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_add, async_cond_map_atomic_add);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_sub, async_cond_map_atomic_sub);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_mul, async_cond_map_atomic_mul);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_div, async_cond_map_atomic_div);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_mod, async_cond_map_atomic_mod);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_and, async_cond_map_atomic_and);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_or, async_cond_map_atomic_or);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_xor, async_cond_map_atomic_xor);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_string_prepend, async_cond_map_string_prepend);
-make_fn_spacename_key_predicates_mapattributes_status!(cond_map_string_append, async_cond_map_string_append);
+make_fn_spacename_key_attributes_status!(put, async_put, true);
+make_fn_spacename_key_predicates_attributes_status!(cond_put, async_cond_put, false);
+make_fn_spacename_key_attributes_status!(put_if_not_exist, async_put_if_not_exist, false);
+make_fn_spacename_key_attributes_status!(atomic_add, async_atomic_add, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_add, async_cond_atomic_add, false);
+make_fn_spacename_key_attributes_status!(atomic_sub, async_atomic_sub, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_sub, async_cond_atomic_sub, false);
+make_fn_spacename_key_attributes_status!(atomic_mul, async_atomic_mul, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_mul, async_cond_atomic_mul, false);
+make_fn_spacename_key_attributes_status!(atomic_div, async_atomic_div, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_div, async_cond_atomic_div, false);
+make_fn_spacename_key_attributes_status!(atomic_mod, async_atomic_mod, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_mod, async_cond_atomic_mod, false);
+make_fn_spacename_key_attributes_status!(atomic_and, async_atomic_and, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_and, async_cond_atomic_and, false);
+make_fn_spacename_key_attributes_status!(atomic_or, async_atomic_or, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_or, async_cond_atomic_or, false);
+make_fn_spacename_key_attributes_status!(atomic_xor, async_atomic_xor, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_atomic_xor, async_cond_atomic_xor, false);
+make_fn_spacename_key_attributes_status!(string_prepend, async_string_prepend, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_string_prepend, async_cond_string_prepend, false);
+make_fn_spacename_key_attributes_status!(string_append, async_string_append, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_string_append, async_cond_string_append, false);
+make_fn_spacename_key_attributes_status!(list_lpush, async_list_lpush, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_list_lpush, async_cond_list_lpush, false);
+make_fn_spacename_key_attributes_status!(list_rpush, async_list_rpush, false);
+make_fn_spacename_key_predicates_attributes_status!(cond_list_rpush, async_cond_list_rpush, false);
+make_fn_spacename_key_attributes_status!(set_add, async_set_add, true);
+make_fn_spacename_key_predicates_attributes_status!(cond_set_add, async_cond_set_add, false);
+make_fn_spacename_key_attributes_status!(set_remove, async_set_remove, true);
+make_fn_spacename_key_predicates_attributes_status!(cond_set_remove, async_cond_set_remove, false);
+make_fn_spacename_key_attributes_status!(set_intersect, async_set_intersect, true);
+make_fn_spacename_key_predicates_attributes_status!(cond_set_intersect, async_cond_set_intersect, false);
+make_fn_spacename_key_attributes_status!(set_union, async_set_union, true);
+make_fn_spacename_key_predicates_attributes_status!(cond_set_union, async_cond_set_union, false);
+make_fn_spacename_key_mapkeys_status!(map_remove, async_map_remove, true);
+make_fn_spacename_key_predicates_mapkeys_status!(cond_map_remove, async_cond_map_remove, false);
+make_fn_spacename_key_docpath_value_status!(doc_set, async_doc_set, async_put, true);
+make_fn_spacename_key_docpath_value_status!(doc_atomic_add, async_doc_atomic_add, async_atomic_add, false);
+make_fn_spacename_key_docpath_value_status!(doc_atomic_sub, async_doc_atomic_sub, async_atomic_sub, false);
+make_fn_spacename_key_docpath_value_status!(doc_string_prepend, async_doc_string_prepend, async_string_prepend, false);
+make_fn_spacename_key_docpath_value_status!(doc_string_append, async_doc_string_append, async_string_append, false);
+make_fn_spacename_key_mapattributes_status!(map_add, async_map_add, true);
+make_fn_spacename_key_mapattributes_status!(map_atomic_add, async_map_atomic_add, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_sub, async_map_atomic_sub, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_mul, async_map_atomic_mul, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_div, async_map_atomic_div, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_mod, async_map_atomic_mod, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_and, async_map_atomic_and, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_or, async_map_atomic_or, false);
+make_fn_spacename_key_mapattributes_status!(map_atomic_xor, async_map_atomic_xor, false);
+make_fn_spacename_key_mapattributes_status!(map_string_prepend, async_map_string_prepend, false);
+make_fn_spacename_key_mapattributes_status!(map_string_append, async_map_string_append, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_add, async_cond_map_add, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_add, async_cond_map_atomic_add, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_sub, async_cond_map_atomic_sub, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_mul, async_cond_map_atomic_mul, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_div, async_cond_map_atomic_div, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_mod, async_cond_map_atomic_mod, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_and, async_cond_map_atomic_and, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_or, async_cond_map_atomic_or, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_atomic_xor, async_cond_map_atomic_xor, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_string_prepend, async_cond_map_string_prepend, false);
+make_fn_spacename_key_predicates_mapattributes_status!(cond_map_string_append, async_cond_map_string_append, false);
@@ -1,18 +1,26 @@
-use std::collections::{HashMap, BTreeSet};
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet, VecDeque};
+use std::collections::hash_map;
+use std::any::Any;
+use std::mem;
 use std::mem::transmute;
 use std::hash::{Hash, Hasher};
-use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::mpsc::{channel, Sender, TrySendError};
+use std::sync::Arc;
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
 use std::iter::FromIterator;
 use std::cmp::Ordering;
 use std::ptr::Unique;
 use std::hash;
 use std::fmt::Debug;
+use std::{i32, u32, i64};
 
 use rustc_serialize::json::Json;
 
 use libc::*;
 
 use common::*;
+use future::{EventReceiver, EventSender, ReplySender, event_channel};
 
 use hyperdex_client::*;
 use hyperdex_datastructures::*;
@@ -56,20 +64,244 @@ pub struct SearchState {
     pub status: Box<Enum_hyperdex_client_returncode>,
     pub attrs: Box<AttributePtr>,
     pub attrs_sz: Box<size_t>,
-    pub res_tx: Sender<Result<HyperObject, HyperError>>,
+    pub res_tx: EventSender,
+    pub cancelled: Arc<AtomicBool>,
+    // Events that did not fit in the stream's buffer, oldest first
+    pub overflow: VecDeque<SearchEvent>,
+    pub overflow_limit: usize,
+    // Whether HyperDex has reported that the search is done
+    pub done: bool,
+    // The deadline in nanoseconds, if there is one
+    pub deadline: Option<u64>,
+    // Whether the deadline has passed, so that the stream has been ended with an error
+    pub expired: bool,
 }
 
+impl SearchState {
+    /// Queues an event for the stream and hands over as many queued events as fit in its
+    /// buffer, without blocking.
+    pub fn deliver(&mut self, event: SearchEvent) {
+        self.overflow.push_back(event);
+        self.flush();
+    }
+
+    /// Hands over as many queued events as fit in the stream's buffer, without blocking.
+    pub fn flush(&mut self) {
+        if self.cancelled.load(atomic::Ordering::Relaxed) {
+            self.overflow.clear();
+            return;
+        }
+
+        while let Some(event) = self.overflow.pop_front() {
+            match self.res_tx.try_send(event) {
+                Ok(()) => (),
+                Err(TrySendError::Full(event)) => {
+                    self.overflow.push_front(event);
+                    return;
+                },
+                Err(TrySendError::Disconnected(_)) => {
+                    // The stream has been dropped
+                    self.cancelled.store(true, atomic::Ordering::Relaxed);
+                    self.overflow.clear();
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Whether the stream is so far behind that no more results should be pulled for it.
+    pub fn is_full(&self) -> bool {
+        !self.done && !self.expired && self.overflow.len() >= self.overflow_limit
+    }
+
+    /// Whether the search is over and every event has been handed to the stream.
+    pub fn is_finished(&self) -> bool {
+        self.done && self.overflow.is_empty()
+    }
+}
+
+pub enum SearchEvent {
+    Item(Result<HyperObject, HyperError>),
+    Done,  // HyperDex reported HYPERDEX_CLIENT_SEARCHDONE
+}
+
+/// The results of a search.
+///
+/// Results are buffered up to a fixed bound, and results that arrive while the buffer is full
+/// are queued, up to the same bound again.  Once that queue is full too, the inner client
+/// that runs the search stops pulling replies from HyperDex until the stream is read, unless
+/// other operations are waiting on that inner client, so that those still complete.  The C
+/// API cannot pause a single search, so the queue may then grow past its bound.
+///
+/// Dropping the stream cancels the search: results that are still in flight are discarded
+/// as they arrive, without being converted into `HyperObject`s.  The C API has no way to
+/// abort a search, so the servers still finish their side of it.
+///
+/// The event loop wakes the stream whenever it hands it results.  With the `std-future`
+/// feature, `SearchStream` also implements `futures::Stream`, so results can be awaited
+/// instead of blocking on `next`.
+pub struct SearchStream {
+    res_rx: EventReceiver,
+    cancelled: Arc<AtomicBool>,
+    done: bool,
+}
+
+impl SearchStream {
+    pub fn new(res_rx: EventReceiver, cancelled: Arc<AtomicBool>) -> SearchStream {
+        SearchStream {
+            res_rx: res_rx,
+            cancelled: cancelled,
+            done: false,
+        }
+    }
+
+    /// Creates a stream that yields a single error and then ends.
+    pub fn from_error(err: HyperError) -> SearchStream {
+        let (res_tx, res_rx) = event_channel(2);
+        res_tx.try_send(SearchEvent::Item(Err(err)));
+        res_tx.try_send(SearchEvent::Done);
+        SearchStream::new(res_rx, Arc::new(AtomicBool::new(true)))
+    }
+
+    /// Turns what the stream received into its next item.  `None` means that the event loop
+    /// dropped the search.
+    fn take_event(&mut self, event: Option<SearchEvent>) -> Option<Result<HyperObject, HyperError>> {
+        match event {
+            Some(SearchEvent::Item(res)) => Some(res),
+            Some(SearchEvent::Done) => {
+                self.done = true;
+                None
+            },
+            None => {
+                self.done = true;
+                Some(Err(HyperError::new(HyperErrorKind::Internal, "the search ended before HyperDex reported that it was done")))
+            },
+        }
+    }
+}
+
+impl Iterator for SearchStream {
+    type Item = Result<HyperObject, HyperError>;
+
+    fn next(&mut self) -> Option<Result<HyperObject, HyperError>> {
+        if self.done {
+            return None;
+        }
+
+        let event = self.res_rx.rx.recv().ok();
+        self.take_event(event)
+    }
+}
+
+#[cfg(feature = "std-future")]
+impl ::futures_core::Stream for SearchStream {
+    type Item = Result<HyperObject, HyperError>;
+
+    fn poll_next(self: ::std::pin::Pin<&mut Self>, cx: &mut ::std::task::Context)
+        -> ::std::task::Poll<Option<Result<HyperObject, HyperError>>> {
+        use std::sync::mpsc::TryRecvError;
+        use std::task::Poll;
+
+        let stream = self.get_mut();
+        if stream.done {
+            return Poll::Ready(None);
+        }
+
+        let received = stream.res_rx.rx.try_recv();
+        match received {
+            Ok(event) => Poll::Ready(stream.take_event(Some(event))),
+            Err(TryRecvError::Disconnected) => Poll::Ready(stream.take_event(None)),
+            Err(TryRecvError::Empty) => {
+                let waker = cx.waker().clone();
+                stream.res_rx.notify.wait(Box::new(move|| waker.wake_by_ref()));
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl Drop for SearchStream {
+    fn drop(&mut self) {
+        self.cancelled.store(true, atomic::Ordering::Relaxed);
+    }
+}
+
+/// The buffers that HyperDex writes the reply to a request into.
+pub type ReplyBuffers = Box<Any + Send>;
+
 pub enum HyperState {
-    HyperStateOp(Sender<HyperError>),  // for calls that don't return values
+    // for calls that don't return values, with the buffers that are handed back once
+    // HyperDex has replied into them and the deadline in nanoseconds if there is one
+    HyperStateOp(ReplySender, ReplyBuffers, Option<u64>),
     HyperStateSearch(SearchState),  // for calls that do return values
 }
 
+/// How long the buffers of a request that timed out are kept for HyperDex's reply, in
+/// milliseconds.
+pub const EXPIRED_OP_TTL_MS: u64 = 60_000;
+
+/// Requests that timed out before HyperDex replied.
+///
+/// HyperDex still writes its reply into the buffers of such a request, so they are kept
+/// until the reply arrives.  A request that has not been answered after `EXPIRED_OP_TTL_MS`
+/// is given up on: its buffers are leaked rather than freed under HyperDex, and a reply that
+/// still comes is treated as one to an unknown request.
+pub struct ExpiredOps {
+    buffers: HashMap<int64_t, ReplyBuffers>,
+    // Request ids with the time they expired at in nanoseconds, oldest first
+    order: VecDeque<(u64, int64_t)>,
+}
+
+impl ExpiredOps {
+    pub fn new() -> ExpiredOps {
+        ExpiredOps {
+            buffers: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Keeps the buffers of a request that expired at `now` until HyperDex replies.
+    pub fn insert(&mut self, reqid: int64_t, buffers: ReplyBuffers, now: u64) {
+        self.buffers.insert(reqid, buffers);
+        self.order.push_back((now, reqid));
+    }
+
+    /// Handles a reply, returning whether it belongs to a request that has expired.  The
+    /// request's buffers are freed.
+    pub fn reply(&mut self, reqid: int64_t) -> bool {
+        self.buffers.remove(&reqid).is_some()
+    }
+
+    /// Gives up on the requests that expired more than `EXPIRED_OP_TTL_MS` before `now`.
+    pub fn age_out(&mut self, now: u64) {
+        loop {
+            let (expired_at, reqid) = match self.order.front() {
+                Some(&entry) => entry,
+                None => break,
+            };
+            if expired_at + EXPIRED_OP_TTL_MS * 1_000_000 > now {
+                break;
+            }
+            self.order.pop_front();
+            if let Some(buffers) = self.buffers.remove(&reqid) {
+                mem::forget(buffers);
+            }
+        }
+    }
+
+    /// The number of requests whose reply is still expected.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+}
+
 pub struct Request {
     id: int64_t,
     confirm_tx: Sender<bool>,
 }
 
 /// Predicates that HyperDex supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HyperPredicateType {
     FAIL = HYPERPREDICATE_FAIL as isize,
     EQUALS = HYPERPREDICATE_EQUALS as isize,
@@ -77,6 +309,7 @@ pub enum HyperPredicateType {
     LESS_EQUAL = HYPERPREDICATE_LESS_EQUAL as isize,
     GREATER_EQUAL = HYPERPREDICATE_GREATER_EQUAL as isize,
     GREATER_THAN = HYPERPREDICATE_GREATER_THAN as isize,
+    CONTAINS_LESS_THAN = HYPERPREDICATE_CONTAINS_LESS_THAN as isize,
     REGEX = HYPERPREDICATE_REGEX as isize,
     LENGTH_EQUALS = HYPERPREDICATE_LENGTH_EQUALS as isize,
     LENGTH_LESS_EQUAL = HYPERPREDICATE_LENGTH_LESS_EQUAL as isize,
@@ -89,9 +322,10 @@ pub enum HyperPredicateType {
 /// # Examples
 /// 
 /// ```
-/// let predicates = vec!(HyperPredicate::new("age", LESS_EQUAL, 25));
+/// let predicates = vec!(HyperPredicate::new("age", LESS_EQUAL, 25).unwrap());
 /// let res = client.search(space_name, predicates);
 /// ```
+#[derive(Debug, Clone, PartialEq)]
 pub struct HyperPredicate {
     pub attr: String,
     pub value: HyperValue,
@@ -99,23 +333,211 @@ pub struct HyperPredicate {
 }
 
 impl HyperPredicate {
+    /// Creates a predicate, checking that the value is of a type that the predicate accepts.
+    ///
+    /// For instance, `REGEX` requires a string and the `LENGTH_*` predicates require an int.
     pub fn new<A, T>(attr: A, predicate: HyperPredicateType, value: T)
-        -> HyperPredicate where A: ToString, T: ToHyperValue {
-        HyperPredicate {
+        -> Result<HyperPredicate, HyperError> where A: ToString, T: TryToHyperValue {
+        let value = match value.try_to_hyper() {
+            Ok(value) => value,
+            Err(err) => return Err(HyperError::new(HyperErrorKind::InvalidArgument, err)),
+        };
+        let pred = HyperPredicate {
             attr: attr.to_string(),
-            value: value.to_hyper(),
+            value: value,
             predicate: predicate,
+        };
+        match pred.validate() {
+            Ok(()) => Ok(pred),
+            Err(err) => Err(HyperError::new(HyperErrorKind::InvalidArgument, err)),
+        }
+    }
+
+    /// Creates a predicate on a field nested inside a document attribute.
+    ///
+    /// The path is the attribute name followed by the field names, separated by dots,
+    /// e.g. `HyperPredicate::doc_path("profile.age", GREATER_EQUAL, 18)`.
+    pub fn doc_path<A, T>(path: A, predicate: HyperPredicateType, value: T)
+        -> Result<HyperPredicate, HyperError> where A: ToString, T: TryToHyperValue {
+        let path = path.to_string();
+        match check_doc_path(path.as_str()) {
+            Ok(()) => HyperPredicate::new(path, predicate, value),
+            Err(err) => Err(HyperError::new(HyperErrorKind::InvalidArgument, err)),
         }
     }
+
+    /// Checks that the value is of a type that the predicate accepts.
+    pub fn validate(&self) -> Result<(), String> {
+        let scalar = match self.value {
+            HyperString(_) | HyperInt(_) | HyperFloat(_) => true,
+            _ => false,
+        };
+        let ok = match self.predicate {
+            HyperPredicateType::FAIL | HyperPredicateType::EQUALS => true,
+            HyperPredicateType::LESS_THAN | HyperPredicateType::LESS_EQUAL |
+            HyperPredicateType::GREATER_EQUAL | HyperPredicateType::GREATER_THAN |
+            HyperPredicateType::CONTAINS | HyperPredicateType::CONTAINS_LESS_THAN => scalar,
+            HyperPredicateType::REGEX => match self.value {
+                HyperString(_) => true,
+                _ => false,
+            },
+            HyperPredicateType::LENGTH_EQUALS | HyperPredicateType::LENGTH_LESS_EQUAL |
+            HyperPredicateType::LENGTH_GREATER_EQUAL => match self.value {
+                HyperInt(_) => true,
+                _ => false,
+            },
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("predicate {:?} on attribute {} does not accept the value {:?}",
+                        self.predicate, self.attr, self.value))
+        }
+    }
+}
+
+/// Checks that `path` names a field inside a document, i.e. that it consists of at least
+/// two non-empty components separated by dots.
+pub fn check_doc_path(path: &str) -> Result<(), String> {
+    let parts: Vec<&str> = path.split('.').collect();
+    if parts.len() < 2 {
+        Err(format!("{} is not a document path; expected attribute.field", path))
+    } else if parts.iter().any(|p| p.is_empty()) {
+        Err(format!("document path {} contains an empty component", path))
+    } else {
+        Ok(())
+    }
 }
 
 /// A key-value pair associated with a specific map attribute
+#[derive(Debug, Clone, PartialEq)]
 pub struct HyperMapAttribute {
     pub attr: String,
     pub key: HyperValue,
     pub value: HyperValue,
 }
 
+/// How a server chose to execute a search.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchStrategy {
+    /// The server walks the given index iterator, as HyperDex describes it, instead of all
+    /// of its objects.
+    Index(String),
+
+    /// The server has to scan every object in its region.
+    FullScan,
+
+    /// The predicates cannot match anything, so the server returns no objects.
+    Empty,
+
+    /// The server's description does not say which iterator it chose.
+    Unknown,
+}
+
+/// The part of a search description reported by a single server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerSearchDescription {
+    pub server: String,
+    pub strategy: SearchStrategy,
+    pub details: Vec<String>,
+}
+
+/// The query plan that HyperDex chose for a search, as returned by `Client::describe_search`.
+///
+/// Every server that the search is routed to reports a `server(<id>)` entry, which continues
+/// on the following lines with the steps of the daemon's planner:
+///
+/// ```text
+/// server(3)  considering attr 2 Range: ...
+///  accessing all objects has cost 1024
+///  iterator index_iterator(...) has cost 12
+///  choosing to use index_iterator(...)
+///  returned 3 objects
+/// ```
+///
+/// The iterator named on the `choosing to use` line is the strategy: a `region_iterator`
+/// walks every object, and any other iterator uses an index.  A server that reports an
+/// invalid range returns no objects.  The original text is kept in `raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchDescription {
+    pub servers: Vec<ServerSearchDescription>,
+    pub raw: String,
+}
+
+impl SearchDescription {
+    pub fn parse(raw: &str) -> SearchDescription {
+        let mut servers: Vec<ServerSearchDescription> = Vec::new();
+
+        for line in raw.lines() {
+            let line = line.trim();
+            if line.len() == 0 {
+                continue;
+            }
+
+            let first = line.split_whitespace().next().unwrap_or("");
+            if first.starts_with("server(") && first.ends_with(")") {
+                servers.push(ServerSearchDescription {
+                    server: first.to_string(),
+                    strategy: SearchStrategy::Unknown,
+                    details: Vec::new(),
+                });
+                let rest = line[first.len()..].trim();
+                if rest.len() > 0 {
+                    servers.last_mut().unwrap().details.push(rest.to_string());
+                }
+            } else if let Some(server) = servers.last_mut() {
+                // A server's text spans several lines
+                server.details.push(line.to_string());
+            }
+            // Lines before the first server are only kept in `raw`
+        }
+
+        for server in servers.iter_mut() {
+            server.strategy = SearchDescription::parse_strategy(&server.details);
+        }
+
+        SearchDescription {
+            servers: servers,
+            raw: raw.to_string(),
+        }
+    }
+
+    fn parse_strategy(details: &Vec<String>) -> SearchStrategy {
+        for detail in details.iter() {
+            if detail.starts_with("encountered invalid range") {
+                return SearchStrategy::Empty;
+            }
+            if detail.starts_with("choosing to use ") {
+                let iterator = detail["choosing to use ".len()..].trim();
+                return if iterator.starts_with("region_iterator") {
+                    SearchStrategy::FullScan
+                } else {
+                    SearchStrategy::Index(iterator.to_string())
+                };
+            }
+        }
+        SearchStrategy::Unknown
+    }
+
+    /// The number of servers that the search touches.
+    pub fn num_servers(&self) -> usize {
+        self.servers.len()
+    }
+
+    /// Whether any server has to scan all of its objects to answer the search, or `None` if
+    /// that cannot be told because a server did not say which iterator it chose.
+    pub fn is_full_scan(&self) -> Option<bool> {
+        if self.servers.iter().any(|server| server.strategy == SearchStrategy::FullScan) {
+            Some(true)
+        } else if self.servers.iter().any(|server| server.strategy == SearchStrategy::Unknown) {
+            None
+        } else {
+            Some(false)
+        }
+    }
+}
+
 /// The errors that can occur upon a lookup from a HyperObject.
 #[derive(Debug)]
 pub enum HyperObjectKeyError {
@@ -124,6 +546,12 @@ pub enum HyperObjectKeyError {
 
     /// The key does exist, but the value is not the type that you think it is.
     ObjectIsAnotherType,
+
+    /// The value is a string, but it is not valid UTF-8.
+    InvalidUtf8,
+
+    /// The value is a number that does not fit in the requested type.
+    OutOfRange,
 }
 
 use std::fmt::{Display, Formatter, Error};
@@ -132,13 +560,20 @@ impl Display for HyperObjectKeyError {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         Display::fmt(match *self {
             HyperObjectKeyError::KeyDoesNotExist => "the key does not exist",
-            HyperObjectKeyError::ObjectIsAnotherType => "the object is of another type"
+            HyperObjectKeyError::ObjectIsAnotherType => "the object is of another type",
+            HyperObjectKeyError::InvalidUtf8 => "the string is not valid UTF-8",
+            HyperObjectKeyError::OutOfRange => "the number does not fit in the requested type",
         }, f)
     }
 }
 
 pub trait FromHyperValue {
     fn from_hyper(val: HyperValue) -> Result<Self, HyperObjectKeyError>;
+
+    /// Called when the attribute is missing from the object.
+    fn from_missing() -> Result<Self, HyperObjectKeyError> {
+        Err(KeyDoesNotExist)
+    }
 }
 
 macro_rules! from_hypervalue_impl(
@@ -182,6 +617,108 @@ from_hypervalue_impl!(HashMap<F64, f64>, HyperMapFloatFloat);
 
 from_hypervalue_impl!(Json, HyperDocument);
 
+impl FromHyperValue for String {
+    fn from_hyper(val: HyperValue) -> Result<String, HyperObjectKeyError> {
+        match val {
+            HyperString(s) => String::from_utf8(s).map_err(|_| InvalidUtf8),
+            _ => Err(ObjectIsAnotherType),
+        }
+    }
+}
+
+// `$min` and `$max` are the bounds of `$t` as an i64 and a u64; `$max` is only compared
+// against non-negative values.
+macro_rules! from_hypervalue_int_impl(
+    ($t: ty, $min: expr, $max: expr) => (
+        impl FromHyperValue for $t {
+            fn from_hyper(val: HyperValue) -> Result<$t, HyperObjectKeyError> {
+                match val {
+                    HyperInt(i) => {
+                        if i >= $min && (i < 0 || (i as u64) <= $max) {
+                            Ok(i as $t)
+                        } else {
+                            Err(OutOfRange)
+                        }
+                    },
+                    _ => Err(ObjectIsAnotherType),
+                }
+            }
+        }
+    )
+);
+
+from_hypervalue_int_impl!(i32, i32::MIN as i64, i32::MAX as u64);
+from_hypervalue_int_impl!(u32, 0, u32::MAX as u64);
+from_hypervalue_int_impl!(u64, 0, i64::MAX as u64);
+
+/// Booleans are stored as ints; any non-zero value is true.
+impl FromHyperValue for bool {
+    fn from_hyper(val: HyperValue) -> Result<bool, HyperObjectKeyError> {
+        match val {
+            HyperInt(i) => Ok(i != 0),
+            _ => Err(ObjectIsAnotherType),
+        }
+    }
+}
+
+/// A missing attribute is read as `None`.
+impl<T: FromHyperValue> FromHyperValue for Option<T> {
+    fn from_hyper(val: HyperValue) -> Result<Option<T>, HyperObjectKeyError> {
+        FromHyperValue::from_hyper(val).map(Some)
+    }
+
+    fn from_missing() -> Result<Option<T>, HyperObjectKeyError> {
+        Ok(None)
+    }
+}
+
+impl<T: FromHyperValue + Hash + Eq> FromHyperValue for HashSet<T> {
+    fn from_hyper(val: HyperValue) -> Result<HashSet<T>, HyperObjectKeyError> {
+        let vals: Vec<HyperValue> = match val {
+            HyperSetString(set) => set.into_iter().map(HyperString).collect(),
+            HyperSetInt(set) => set.into_iter().map(HyperInt).collect(),
+            HyperSetFloat(set) => set.into_iter().map(|F64(f)| HyperFloat(f)).collect(),
+            _ => return Err(ObjectIsAnotherType),
+        };
+        let mut res = HashSet::new();
+        for v in vals.into_iter() {
+            res.insert(try!(FromHyperValue::from_hyper(v)));
+        }
+        Ok(res)
+    }
+}
+
+impl<K: FromHyperValue + Ord, V: FromHyperValue> FromHyperValue for BTreeMap<K, V> {
+    fn from_hyper(val: HyperValue) -> Result<BTreeMap<K, V>, HyperObjectKeyError> {
+        let pairs: Vec<(HyperValue, HyperValue)> = match val {
+            HyperMapStringString(m) => m.into_iter().map(|(k, v)| (HyperString(k), HyperString(v))).collect(),
+            HyperMapStringInt(m) => m.into_iter().map(|(k, v)| (HyperString(k), HyperInt(v))).collect(),
+            HyperMapStringFloat(m) => m.into_iter().map(|(k, v)| (HyperString(k), HyperFloat(v))).collect(),
+            HyperMapIntString(m) => m.into_iter().map(|(k, v)| (HyperInt(k), HyperString(v))).collect(),
+            HyperMapIntInt(m) => m.into_iter().map(|(k, v)| (HyperInt(k), HyperInt(v))).collect(),
+            HyperMapIntFloat(m) => m.into_iter().map(|(k, v)| (HyperInt(k), HyperFloat(v))).collect(),
+            HyperMapFloatString(m) => m.into_iter().map(|(F64(k), v)| (HyperFloat(k), HyperString(v))).collect(),
+            HyperMapFloatInt(m) => m.into_iter().map(|(F64(k), v)| (HyperFloat(k), HyperInt(v))).collect(),
+            HyperMapFloatFloat(m) => m.into_iter().map(|(F64(k), v)| (HyperFloat(k), HyperFloat(v))).collect(),
+            _ => return Err(ObjectIsAnotherType),
+        };
+        let mut res = BTreeMap::new();
+        for (k, v) in pairs.into_iter() {
+            res.insert(try!(FromHyperValue::from_hyper(k)), try!(FromHyperValue::from_hyper(v)));
+        }
+        Ok(res)
+    }
+}
+
+impl FromHyperValue for F64 {
+    fn from_hyper(val: HyperValue) -> Result<F64, HyperObjectKeyError> {
+        match val {
+            HyperFloat(f) => Ok(F64(f)),
+            _ => Err(ObjectIsAnotherType),
+        }
+    }
+}
+
 /// A HyperDex object.
 ///
 /// # Examples
@@ -205,7 +742,7 @@ from_hypervalue_impl!(Json, HyperDocument);
 ///     Err(err) => panic!(err),
 /// }
 /// ```
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct HyperObject {
     pub map: HashMap<String, HyperValue>,
 }
@@ -221,6 +758,24 @@ impl HyperObject {
         self.map.insert(attr.to_string(), val.to_hyper());
     }
 
+    /// Like `insert`, but also takes values that may not be storable, such as a `u64` or an
+    /// `Option`.  `None` removes the attribute, and a value that cannot be stored is an
+    /// error that leaves the object unchanged.
+    pub fn try_insert<K, V>(&mut self, attr: K, val: V) -> Result<(), HyperObjectKeyError>
+        where K: ToString, V: TryToHyperValue {
+        match val.try_to_hyper() {
+            Ok(val) => {
+                self.map.insert(attr.to_string(), val);
+                Ok(())
+            },
+            Err(KeyDoesNotExist) => {
+                self.map.remove(&attr.to_string());
+                Ok(())
+            },
+            Err(err) => Err(err),
+        }
+    }
+
     pub fn get<K, T>(&self, attr: K) -> Result<T, HyperObjectKeyError> where K: ToString, T: FromHyperValue {
         let val_opt = self.map.get(&attr.to_string());
         match val_opt {
@@ -231,10 +786,104 @@ impl HyperObject {
                 }
             },
             None => {
-                Err(KeyDoesNotExist)
+                FromHyperValue::from_missing()
             }
         }
     }
+
+    /// Returns a reference to the value of an attribute, without cloning it.
+    pub fn get_ref<K>(&self, attr: K) -> Result<&HyperValue, HyperObjectKeyError> where K: ToString {
+        match self.map.get(&attr.to_string()) {
+            Some(val) => Ok(val),
+            None => Err(KeyDoesNotExist),
+        }
+    }
+
+    pub fn remove<K>(&mut self, attr: K) -> Option<HyperValue> where K: ToString {
+        self.map.remove(&attr.to_string())
+    }
+
+    pub fn contains<K>(&self, attr: K) -> bool where K: ToString {
+        self.map.contains_key(&attr.to_string())
+    }
+
+    pub fn keys(&self) -> hash_map::Keys<String, HyperValue> {
+        self.map.keys()
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<String, HyperValue> {
+        self.map.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Moves every attribute of `other` into this object, overwriting attributes that
+    /// exist in both.
+    pub fn merge(&mut self, other: HyperObject) {
+        self.map.extend(other.map.into_iter());
+    }
+}
+
+impl IntoIterator for HyperObject {
+    type Item = (String, HyperValue);
+    type IntoIter = hash_map::IntoIter<String, HyperValue>;
+
+    fn into_iter(self) -> hash_map::IntoIter<String, HyperValue> {
+        self.map.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a HyperObject {
+    type Item = (&'a String, &'a HyperValue);
+    type IntoIter = hash_map::Iter<'a, String, HyperValue>;
+
+    fn into_iter(self) -> hash_map::Iter<'a, String, HyperValue> {
+        self.map.iter()
+    }
+}
+
+impl<K: ToString, V: ToHyperValue> FromIterator<(K, V)> for HyperObject {
+    fn from_iter<I: IntoIterator<Item=(K, V)>>(iter: I) -> HyperObject {
+        let mut obj = HyperObject::new();
+        obj.extend(iter);
+        obj
+    }
+}
+
+impl<K: ToString, V: ToHyperValue> Extend<(K, V)> for HyperObject {
+    fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, iter: I) {
+        for (attr, val) in iter {
+            self.insert(attr, val);
+        }
+    }
+}
+
+/// Types that can be stored as a HyperDex object.
+///
+/// Usually derived with `#[derive(HyperObject)]` from the `hyperdex_derive` crate.
+pub trait ToHyperObject {
+    /// Converts the value into an object.  The key is not included; see `HyperKey`.
+    fn to_hyper_object(self) -> Result<HyperObject, HyperObjectKeyError>;
+}
+
+/// Types that can be built from a HyperDex object.
+///
+/// Usually derived with `#[derive(HyperObject)]` from the `hyperdex_derive` crate.
+pub trait FromHyperObject {
+    fn from_hyper_object(obj: HyperObject) -> Result<Self, HyperObjectKeyError>;
+}
+
+/// Types that carry the key they are stored under.
+///
+/// Derived along with `ToHyperObject` for a struct that has a `#[hyper(key)]` field.
+pub trait HyperKey {
+    fn hyper_key(&self) -> Result<HyperValue, HyperObjectKeyError>;
 }
 
 pub trait ToByteVec {
@@ -269,6 +918,27 @@ pub trait ToHyperValue {
     fn to_hyper(self) -> HyperValue;
 }
 
+/// Values that may not convert to a `HyperValue`.
+///
+/// Every `ToHyperValue` converts.  A `u64` above `i64::MAX` is `OutOfRange`, and `None` is
+/// `KeyDoesNotExist`.  Keys, values and predicates given to a `Client` may be of any of
+/// these types, and a value that does not convert is reported as an error.
+pub trait TryToHyperValue {
+    fn try_to_hyper(self) -> Result<HyperValue, HyperObjectKeyError>;
+}
+
+impl<T: ToHyperValue> TryToHyperValue for T {
+    fn try_to_hyper(self) -> Result<HyperValue, HyperObjectKeyError> {
+        Ok(self.to_hyper())
+    }
+}
+
+impl ToHyperValue for HyperValue {
+    fn to_hyper(self) -> HyperValue {
+        self
+    }
+}
+
 impl<'a> ToHyperValue for &'a str {
     fn to_hyper(self) -> HyperValue {
         let s = self.to_string();
@@ -444,6 +1114,141 @@ impl ToHyperValue for HashMap<F64, f64> {
     }
 }
 
+impl ToHyperValue for i32 {
+    fn to_hyper(self) -> HyperValue {
+        HyperInt(self as i64)
+    }
+}
+
+impl ToHyperValue for u32 {
+    fn to_hyper(self) -> HyperValue {
+        HyperInt(self as i64)
+    }
+}
+
+/// HyperDex ints are signed, so values above `i64::MAX` are out of range.
+impl TryToHyperValue for u64 {
+    fn try_to_hyper(self) -> Result<HyperValue, HyperObjectKeyError> {
+        if self <= i64::MAX as u64 {
+            Ok(HyperInt(self as i64))
+        } else {
+            Err(OutOfRange)
+        }
+    }
+}
+
+/// `None` has no value.  `HyperObject::try_insert` leaves it out of the object, so that it
+/// is read back as `None`.
+impl<T: TryToHyperValue> TryToHyperValue for Option<T> {
+    fn try_to_hyper(self) -> Result<HyperValue, HyperObjectKeyError> {
+        match self {
+            Some(val) => val.try_to_hyper(),
+            None => Err(KeyDoesNotExist),
+        }
+    }
+}
+
+/// Booleans are stored as the ints 0 and 1.
+impl ToHyperValue for bool {
+    fn to_hyper(self) -> HyperValue {
+        HyperInt(if self { 1 } else { 0 })
+    }
+}
+
+impl ToHyperValue for F64 {
+    fn to_hyper(self) -> HyperValue {
+        HyperFloat(self.0)
+    }
+}
+
+impl<'a> ToHyperValue for HashSet<&'a str> {
+    fn to_hyper(self) -> HyperValue {
+        HyperSetString(FromIterator::from_iter(self.into_iter().map(|s| {
+            s.as_bytes().to_vec()
+        })))
+    }
+}
+
+impl ToHyperValue for HashSet<String> {
+    fn to_hyper(self) -> HyperValue {
+        HyperSetString(FromIterator::from_iter(self.into_iter().map(|s| {
+            s.into_bytes()
+        })))
+    }
+}
+
+impl ToHyperValue for HashSet<Vec<u8>> {
+    fn to_hyper(self) -> HyperValue {
+        HyperSetString(FromIterator::from_iter(self.into_iter()))
+    }
+}
+
+impl ToHyperValue for HashSet<i64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperSetInt(FromIterator::from_iter(self.into_iter()))
+    }
+}
+
+impl ToHyperValue for HashSet<F64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperSetFloat(FromIterator::from_iter(self.into_iter()))
+    }
+}
+
+impl<K: ToByteVec + Ord, V: ToByteVec> ToHyperValue for BTreeMap<K, V> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapStringString(self.into_iter().map(|(k, v)| (k.to_bytes(), v.to_bytes())).collect())
+    }
+}
+
+impl<K: ToByteVec + Ord> ToHyperValue for BTreeMap<K, i64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapStringInt(self.into_iter().map(|(k, v)| (k.to_bytes(), v)).collect())
+    }
+}
+
+impl<K: ToByteVec + Ord> ToHyperValue for BTreeMap<K, f64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapStringFloat(self.into_iter().map(|(k, v)| (k.to_bytes(), v)).collect())
+    }
+}
+
+impl<V: ToByteVec> ToHyperValue for BTreeMap<i64, V> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapIntString(self.into_iter().map(|(k, v)| (k, v.to_bytes())).collect())
+    }
+}
+
+impl ToHyperValue for BTreeMap<i64, i64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapIntInt(self.into_iter().collect())
+    }
+}
+
+impl ToHyperValue for BTreeMap<i64, f64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapIntFloat(self.into_iter().collect())
+    }
+}
+
+impl<V: ToByteVec> ToHyperValue for BTreeMap<F64, V> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapFloatString(self.into_iter().map(|(k, v)| (k, v.to_bytes())).collect())
+    }
+}
+
+impl ToHyperValue for BTreeMap<F64, i64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapFloatInt(self.into_iter().collect())
+    }
+}
+
+impl ToHyperValue for BTreeMap<F64, f64> {
+    fn to_hyper(self) -> HyperValue {
+        HyperMapFloatFloat(self.into_iter().collect())
+    }
+}
+
 impl ToHyperValue for Json {
     fn to_hyper(self) -> HyperValue {
         HyperDocument(self)
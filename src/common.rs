@@ -1,28 +1,169 @@
+use std::error;
 use std::ffi::{CStr, CString};
 use std::fmt::{Display, Formatter, Error};
+use std::sync::mpsc::Receiver;
 
 use hyperdex_admin::*;
 use hyperdex_client::*;
 
+/// The kind of a `HyperError`.
+///
+/// Every return code of the client and admin libraries has a kind, apart from the
+/// success codes.  Codes that the two libraries share in meaning, such as `NotFound` and
+/// `Timeout`, map to the same kind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HyperErrorKind {
+    NotFound,
+    CmpFail,
+    ReadOnly,
+    UnknownSpace,
+    CoordFail,
+    ServerError,
+    PollFailed,
+    Overflow,
+    Reconfigure,
+    Timeout,
+    UnknownAttr,
+    DupeAttr,
+    NonePending,
+    DontUseKey,
+    WrongType,
+    NoMem,
+    Interrupted,
+    ClusterJump,
+    Offline,
+    BadSpace,
+    Duplicate,
+    LocalError,
+    Internal,
+    Exception,
+    Garbage,
+
+    /// A value could not be converted to or from its HyperDex representation.
+    Conversion,
+
+    /// A predicate, cursor or other argument was rejected before reaching HyperDex.
+    InvalidArgument,
+
+    /// A space description was rejected.
+    Schema,
+
+    /// The operation did not complete before the timeout set on the client.
+    DeadlineExceeded,
+
+    /// A return code that this crate does not know about.
+    Unknown(u32),
+}
+
+impl HyperErrorKind {
+    pub fn from_client_status(status: u32) -> HyperErrorKind {
+        let kinds = [
+            (HYPERDEX_CLIENT_NOTFOUND, HyperErrorKind::NotFound),
+            (HYPERDEX_CLIENT_CMPFAIL, HyperErrorKind::CmpFail),
+            (HYPERDEX_CLIENT_READONLY, HyperErrorKind::ReadOnly),
+            (HYPERDEX_CLIENT_UNKNOWNSPACE, HyperErrorKind::UnknownSpace),
+            (HYPERDEX_CLIENT_COORDFAIL, HyperErrorKind::CoordFail),
+            (HYPERDEX_CLIENT_SERVERERROR, HyperErrorKind::ServerError),
+            (HYPERDEX_CLIENT_POLLFAILED, HyperErrorKind::PollFailed),
+            (HYPERDEX_CLIENT_OVERFLOW, HyperErrorKind::Overflow),
+            (HYPERDEX_CLIENT_RECONFIGURE, HyperErrorKind::Reconfigure),
+            (HYPERDEX_CLIENT_TIMEOUT, HyperErrorKind::Timeout),
+            (HYPERDEX_CLIENT_UNKNOWNATTR, HyperErrorKind::UnknownAttr),
+            (HYPERDEX_CLIENT_DUPEATTR, HyperErrorKind::DupeAttr),
+            (HYPERDEX_CLIENT_NONEPENDING, HyperErrorKind::NonePending),
+            (HYPERDEX_CLIENT_DONTUSEKEY, HyperErrorKind::DontUseKey),
+            (HYPERDEX_CLIENT_WRONGTYPE, HyperErrorKind::WrongType),
+            (HYPERDEX_CLIENT_NOMEM, HyperErrorKind::NoMem),
+            (HYPERDEX_CLIENT_INTERRUPTED, HyperErrorKind::Interrupted),
+            (HYPERDEX_CLIENT_CLUSTER_JUMP, HyperErrorKind::ClusterJump),
+            (HYPERDEX_CLIENT_OFFLINE, HyperErrorKind::Offline),
+            (HYPERDEX_CLIENT_INTERNAL, HyperErrorKind::Internal),
+            (HYPERDEX_CLIENT_EXCEPTION, HyperErrorKind::Exception),
+            (HYPERDEX_CLIENT_GARBAGE, HyperErrorKind::Garbage),
+        ];
+        lookup_kind(&kinds, status)
+    }
+
+    pub fn from_admin_status(status: u32) -> HyperErrorKind {
+        let kinds = [
+            (HYPERDEX_ADMIN_NOMEM, HyperErrorKind::NoMem),
+            (HYPERDEX_ADMIN_NONEPENDING, HyperErrorKind::NonePending),
+            (HYPERDEX_ADMIN_POLLFAILED, HyperErrorKind::PollFailed),
+            (HYPERDEX_ADMIN_TIMEOUT, HyperErrorKind::Timeout),
+            (HYPERDEX_ADMIN_INTERRUPTED, HyperErrorKind::Interrupted),
+            (HYPERDEX_ADMIN_SERVERERROR, HyperErrorKind::ServerError),
+            (HYPERDEX_ADMIN_COORDFAIL, HyperErrorKind::CoordFail),
+            (HYPERDEX_ADMIN_BADSPACE, HyperErrorKind::BadSpace),
+            (HYPERDEX_ADMIN_DUPLICATE, HyperErrorKind::Duplicate),
+            (HYPERDEX_ADMIN_NOTFOUND, HyperErrorKind::NotFound),
+            (HYPERDEX_ADMIN_LOCALERROR, HyperErrorKind::LocalError),
+            (HYPERDEX_ADMIN_INTERNAL, HyperErrorKind::Internal),
+            (HYPERDEX_ADMIN_EXCEPTION, HyperErrorKind::Exception),
+            (HYPERDEX_ADMIN_GARBAGE, HyperErrorKind::Garbage),
+        ];
+        lookup_kind(&kinds, status)
+    }
+
+    /// Whether an operation that failed with this kind of error may succeed if it is
+    /// retried, e.g. because the cluster was reconfiguring or a coordinator was unreachable.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            HyperErrorKind::Reconfigure | HyperErrorKind::CoordFail | HyperErrorKind::Timeout |
+            HyperErrorKind::Interrupted | HyperErrorKind::ClusterJump => true,
+            _ => false,
+        }
+    }
+}
+
+fn lookup_kind(kinds: &[(u32, HyperErrorKind)], status: u32) -> HyperErrorKind {
+    for &(code, kind) in kinds.iter() {
+        if code == status {
+            return kind;
+        }
+    }
+    HyperErrorKind::Unknown(status)
+}
+
 /// An error related to HyperDex.
 #[derive(Debug, Clone)]
 pub struct HyperError {
-    pub status: u32,
+    pub kind: HyperErrorKind,
     pub message: String,
     pub location: String,
 }
 
+impl HyperError {
+    /// Creates an error that did not come from HyperDex itself, and so has no location.
+    pub fn new<M>(kind: HyperErrorKind, message: M) -> HyperError where M: ToString {
+        HyperError {
+            kind: kind,
+            message: message.to_string(),
+            location: String::new(),
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+    }
+}
+
 impl Display for HyperError {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        Display::fmt(format!("HyperDex error:\n\tstatus: {}\n\tmessage: {}\n\tlocation: {}\n",
-                             self.status, self.message, self.location).as_str(), f)
+        Display::fmt(format!("HyperDex error:\n\tkind: {:?}\n\tmessage: {}\n\tlocation: {}\n",
+                             self.kind, self.message, self.location).as_str(), f)
+    }
+}
+
+impl error::Error for HyperError {
+    fn description(&self) -> &str {
+        self.message.as_str()
     }
 }
 
 pub fn get_admin_error(admin: *mut Struct_hyperdex_admin, status: u32) -> HyperError {
     unsafe {
         HyperError {
-            status: status,
+            kind: HyperErrorKind::from_admin_status(status),
             message: to_string(hyperdex_admin_error_message(admin)),
             location: to_string(hyperdex_admin_error_location(admin)),
         }
@@ -32,42 +173,102 @@ pub fn get_admin_error(admin: *mut Struct_hyperdex_admin, status: u32) -> HyperE
 pub fn get_client_error(client: *mut Struct_hyperdex_client, status: u32) -> HyperError {
     unsafe {
         HyperError {
-            status: status,
+            kind: HyperErrorKind::from_client_status(status),
             message: to_string(hyperdex_client_error_message(client)),
             location: to_string(hyperdex_client_error_location(client)),
         }
     }
 }
 
+/// Waits for the result of a request, failing if the event loop that would have delivered
+/// it has exited.
+pub fn recv_result<T>(rx: &Receiver<Result<T, HyperError>>) -> Result<T, HyperError> {
+    match rx.recv() {
+        Ok(res) => res,
+        Err(_) => Err(HyperError::new(HyperErrorKind::Internal,
+                                      "the event loop exited before the request completed")),
+    }
+}
+
+/// The port that HyperDex coordinators listen on by default.
+pub const DEFAULT_COORDINATOR_PORT: u16 = 1982;
+
+/// Splits a connection string such as "host1:1982,host2" into (host, port) pairs.
+///
+/// Entries without a port use the default coordinator port.  IPv6 addresses must be
+/// bracketed, as in "[::1]:1982"; the brackets are not part of the returned host.
+pub fn parse_conn_str(conn: &str) -> Result<Vec<(String, u16)>, String> {
+    let mut coordinators = Vec::new();
+    for entry in conn.split(',') {
+        let entry = entry.trim();
+        if entry.len() == 0 {
+            return Err(format!("Empty coordinator in connection string: \"{}\"", conn));
+        }
+        let (host, port_str) = if entry.starts_with("[") {
+            match entry.find(']') {
+                Some(idx) if entry[idx + 1..].len() == 0 => (&entry[1..idx], None),
+                Some(idx) if entry[idx + 1..].starts_with(":") => (&entry[1..idx], Some(&entry[idx + 2..])),
+                _ => return Err(format!("Malformed bracketed coordinator \"{}\"", entry)),
+            }
+        } else {
+            match entry.find(':') {
+                Some(idx) if entry[idx + 1..].contains(':') => {
+                    return Err(format!("IPv6 coordinators must be bracketed, as in \"[{}]\"", entry));
+                },
+                Some(idx) => (&entry[..idx], Some(&entry[idx + 1..])),
+                None => (entry, None),
+            }
+        };
+        let port = match port_str {
+            Some(port_str) => match port_str.parse::<u16>() {
+                Ok(port) => port,
+                Err(_) => return Err(format!("Invalid coordinator port in \"{}\"", entry)),
+            },
+            None => DEFAULT_COORDINATOR_PORT,
+        };
+        if host.len() == 0 {
+            return Err(format!("Missing coordinator host in \"{}\"", entry));
+        }
+        coordinators.push((host.to_string(), port));
+    }
+    Ok(coordinators)
+}
+
 pub unsafe fn to_bytes_with_len(ptr: *const ::libc::c_char, len: u64) -> Vec<u8> {
     return Vec::from_raw_buf(ptr as *const u8, len as usize);
 }
 
 pub unsafe fn to_string(ptr: *const ::libc::c_char) -> String {
     let cstr = CStr::from_ptr(ptr);
-    String::from_utf8(cstr.to_bytes().to_vec()).unwrap()
+    String::from_utf8_lossy(cstr.to_bytes()).into_owned()
 }
 
+/// Names that are passed to HyperDex as C strings.
 pub trait ToCStr {
-    fn to_c_str(self) -> CString;
+    /// Fails with a `Conversion` error if the name contains a NUL byte.
+    fn to_c_str(self) -> Result<CString, HyperError>;
 }
 
 impl ToCStr for Vec<u8> {
-    fn to_c_str(self) -> CString {
+    fn to_c_str(self) -> Result<CString, HyperError> {
+        if self.contains(&0) {
+            return Err(HyperError::new(HyperErrorKind::Conversion,
+                                       format!("{:?} contains a NUL byte", String::from_utf8_lossy(self.as_slice()))));
+        }
         unsafe {
-            CString::from_vec_unchecked(self)
+            Ok(CString::from_vec_unchecked(self))
         }
     }
 }
 
 impl ToCStr for String {
-    fn to_c_str(self) -> CString {
-        CString::new(self).unwrap()
+    fn to_c_str(self) -> Result<CString, HyperError> {
+        self.into_bytes().to_c_str()
     }
 }
 
 impl<'a> ToCStr for &'a str {
-    fn to_c_str(self) -> CString {
+    fn to_c_str(self) -> Result<CString, HyperError> {
         self.to_string().to_c_str()
     }
 }
@@ -0,0 +1,261 @@
+use std::any::Any;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, sync_channel, Sender, SyncSender, Receiver, TryRecvError, TrySendError};
+
+#[cfg(feature = "std-future")]
+use std::pin::Pin;
+#[cfg(feature = "std-future")]
+use std::task::{Context, Poll};
+
+use common::*;
+use client_types::{ReplyBuffers, SearchEvent};
+
+/// Wakes whoever waits for a reply, such as the task that last polled a `HyperFuture` or a
+/// `SearchStream`.
+///
+/// A notification that arrives while nobody waits is kept, and handed to the next waiter as
+/// soon as it registers, so that no reply is missed in between.
+pub struct Notify {
+    state: Mutex<NotifyState>,
+}
+
+struct NotifyState {
+    waiter: Option<Box<Fn() + Send>>,
+    pending: bool,
+}
+
+impl Notify {
+    pub fn new() -> Notify {
+        Notify {
+            state: Mutex::new(NotifyState {
+                waiter: None,
+                pending: false,
+            }),
+        }
+    }
+
+    /// Calls `waiter` on the next notification, or right away if one arrived since the last
+    /// waiter was called.  Replaces any waiter that has not been called yet.
+    pub fn wait(&self, waiter: Box<Fn() + Send>) {
+        let mut state = self.state.lock().unwrap();
+        if state.pending {
+            state.pending = false;
+            state.waiter = None;
+            drop(state);
+            waiter();
+        } else {
+            state.waiter = Some(waiter);
+        }
+    }
+
+    /// Calls the waiter, if there is one, or keeps the notification for the next one.
+    pub fn notify(&self) {
+        let mut state = self.state.lock().unwrap();
+        match state.waiter.take() {
+            Some(waiter) => {
+                drop(state);
+                waiter();
+            },
+            None => state.pending = true,
+        }
+    }
+}
+
+/// Hands the reply to an operation from the event loop to its `HyperFuture`.
+pub struct ReplySender {
+    tx: Option<Sender<Result<ReplyBuffers, HyperError>>>,
+    notify: Arc<Notify>,
+}
+
+impl ReplySender {
+    pub fn send(&self, res: Result<ReplyBuffers, HyperError>) {
+        if let Some(ref tx) = self.tx {
+            tx.send(res);
+        }
+        self.notify.notify();
+    }
+}
+
+impl Drop for ReplySender {
+    fn drop(&mut self) {
+        // The future fails once the event loop drops the request unanswered, so it is woken
+        // after the channel is closed
+        self.tx.take();
+        self.notify.notify();
+    }
+}
+
+pub struct ReplyReceiver {
+    rx: Receiver<Result<ReplyBuffers, HyperError>>,
+    notify: Arc<Notify>,
+}
+
+/// Creates the channel over which an operation's reply buffers are handed back.
+pub fn reply_channel() -> (ReplySender, ReplyReceiver) {
+    let (tx, rx) = channel();
+    let notify = Arc::new(Notify::new());
+    (ReplySender { tx: Some(tx), notify: notify.clone() },
+     ReplyReceiver { rx: rx, notify: notify })
+}
+
+/// Hands the events of a search from the event loop to its `SearchStream`.
+pub struct EventSender {
+    tx: Option<SyncSender<SearchEvent>>,
+    notify: Arc<Notify>,
+}
+
+impl EventSender {
+    /// Sends `event` if the stream's buffer has room for it, and wakes the stream.
+    pub fn try_send(&self, event: SearchEvent) -> Result<(), TrySendError<SearchEvent>> {
+        let res = match self.tx {
+            Some(ref tx) => tx.try_send(event),
+            None => Err(TrySendError::Disconnected(event)),
+        };
+        if res.is_ok() {
+            self.notify.notify();
+        }
+        res
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.tx.take();
+        self.notify.notify();
+    }
+}
+
+pub struct EventReceiver {
+    pub rx: Receiver<SearchEvent>,
+    pub notify: Arc<Notify>,
+}
+
+/// Creates the channel over which a search's events are handed to its stream, buffering up
+/// to `bound` of them.
+pub fn event_channel(bound: usize) -> (EventSender, EventReceiver) {
+    let (tx, rx) = sync_channel(bound);
+    let notify = Arc::new(Notify::new());
+    (EventSender { tx: Some(tx), notify: notify.clone() },
+     EventReceiver { rx: rx, notify: notify })
+}
+
+/// Takes back the buffers that HyperDex wrote a reply into.
+pub fn take_buffers<T: Any>(buffers: ReplyBuffers) -> Result<T, HyperError> {
+    match buffers.downcast::<T>() {
+        Ok(buffers) => Ok(*buffers),
+        Err(_) => Err(HyperError::new(HyperErrorKind::Internal,
+                                      "the reply was written into buffers of another type")),
+    }
+}
+
+enum FutureState<T> {
+    Ready(Result<T, HyperError>),
+    Waiting(ReplyReceiver, Box<FnMut(ReplyBuffers) -> Result<T, HyperError> + Send>),
+    Taken,
+}
+
+/// The result of an asynchronous operation, such as `Client::async_get`.
+///
+/// The inner client's event loop wakes the future once HyperDex has replied.  `into_inner`
+/// blocks until then; `try_get` and `on_ready` let an event loop wait for many futures at
+/// once instead.  With the `std-future` feature, which needs a toolchain that has
+/// `std::future`, `HyperFuture` also implements `std::future::Future`, so it can be
+/// awaited.
+///
+/// # Examples
+///
+/// ```
+/// let mut fut = client.async_get(space_name, "derek");
+/// fut.on_ready(move|| ready_tx.send(()).unwrap());
+/// ready_rx.recv().unwrap();
+/// let obj = fut.try_get().unwrap().unwrap();
+/// ```
+pub struct HyperFuture<T> {
+    state: FutureState<T>,
+}
+
+impl<T> HyperFuture<T> {
+    /// A future whose reply is taken from `rx` and turned into the result by `finish`.
+    pub fn new<F>(rx: ReplyReceiver, finish: F) -> HyperFuture<T>
+        where F: FnMut(ReplyBuffers) -> Result<T, HyperError> + Send + 'static {
+        HyperFuture {
+            state: FutureState::Waiting(rx, Box::new(finish)),
+        }
+    }
+
+    /// A future that is already complete.
+    pub fn from_value(res: Result<T, HyperError>) -> HyperFuture<T> {
+        HyperFuture {
+            state: FutureState::Ready(res),
+        }
+    }
+
+    /// Waits for the reply and returns the result.
+    pub fn into_inner(self) -> Result<T, HyperError> {
+        match self.state {
+            FutureState::Ready(res) => res,
+            FutureState::Waiting(rx, mut finish) => finish(try!(recv_result(&rx.rx))),
+            FutureState::Taken => Err(already_taken()),
+        }
+    }
+
+    /// Returns the result if the reply has arrived, without blocking.  The result is handed
+    /// out once; later calls return an `Internal` error.
+    pub fn try_get(&mut self) -> Option<Result<T, HyperError>> {
+        match mem::replace(&mut self.state, FutureState::Taken) {
+            FutureState::Ready(res) => Some(res),
+            FutureState::Waiting(rx, mut finish) => {
+                let received = rx.rx.try_recv();
+                let reply = match received {
+                    Ok(reply) => reply,
+                    Err(TryRecvError::Empty) => {
+                        self.state = FutureState::Waiting(rx, finish);
+                        return None;
+                    },
+                    // Nothing was sent, so this reports that the event loop exited
+                    Err(TryRecvError::Disconnected) => recv_result(&rx.rx),
+                };
+                Some(match reply {
+                    Ok(buffers) => finish(buffers),
+                    Err(err) => Err(err),
+                })
+            },
+            FutureState::Taken => Some(Err(already_taken())),
+        }
+    }
+
+    /// Calls `waiter` from the event loop once the reply has arrived, or right away if it
+    /// already has.  Only the last waiter given is called.  The event loop cannot handle
+    /// other replies while the waiter runs, so it should only hand the news on, not wait
+    /// for the client.
+    pub fn on_ready<F>(&self, waiter: F) where F: Fn() + Send + 'static {
+        match self.state {
+            FutureState::Waiting(ref rx, _) => rx.notify.wait(Box::new(waiter)),
+            _ => waiter(),
+        }
+    }
+}
+
+fn already_taken() -> HyperError {
+    HyperError::new(HyperErrorKind::Internal, "the result of the future has already been taken")
+}
+
+// The state is never pinned in place, so moving the future is always safe
+#[cfg(feature = "std-future")]
+impl<T> Unpin for HyperFuture<T> {}
+
+#[cfg(feature = "std-future")]
+impl<T> ::std::future::Future for HyperFuture<T> {
+    type Output = Result<T, HyperError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T, HyperError>> {
+        let fut = self.get_mut();
+        if let Some(res) = fut.try_get() {
+            return Poll::Ready(res);
+        }
+        let waker = cx.waker().clone();
+        fut.on_ready(move|| waker.wake_by_ref());
+        Poll::Pending
+    }
+}
@@ -16,6 +16,10 @@ pub struct AttributePtr(pub *const Struct_hyperdex_client_attribute);
 unsafe impl Send for AttributePtr {}
 unsafe impl Sync for AttributePtr {}
 
+pub struct CStrPtr(pub *const ::libc::c_char);
+unsafe impl Send for CStrPtr {}
+unsafe impl Sync for CStrPtr {}
+
 unsafe impl Send for Struct_hyperdex_client_attribute {}
 unsafe impl Sync for Struct_hyperdex_client_attribute {}
 
@@ -10,14 +10,31 @@
 
 extern crate libc;
 extern crate rustc_serialize;
+#[cfg(feature = "std-future")]
+extern crate futures_core;
 
-pub use common::HyperError;
-pub use client::{Client};
-pub use client_types::{F64, HyperMapAttribute, HyperObject, HyperPredicate, HyperObjectKeyError, HyperPredicateType, HyperValue};
+pub use common::{HyperError, HyperErrorKind};
+pub use client::{Client, ClientBuilder, WithTimeout};
+pub use client_types::{EXPIRED_OP_TTL_MS, F64, HyperMapAttribute, HyperObject, HyperPredicate, HyperObjectKeyError,
+                       HyperPredicateType, HyperValue,
+                       SearchDescription, SearchStrategy, SearchStream, ServerSearchDescription,
+                       ToHyperValue, TryToHyperValue, FromHyperValue, ToHyperObject, FromHyperObject, HyperKey};
 pub use admin::Admin;
+pub use future::HyperFuture;
+pub use paginator::{Cursor, Paginator};
+pub use query::{Query, QueryExpr, QueryStream, MAX_CONJUNCTS};
+pub use retry::RetryPolicy;
+pub use space::{HyperDataType, SpaceAttribute, SpaceDesc, ToSpaceDesc};
+pub use space_builder::SpaceBuilder;
 
 mod client;
 mod admin;
+mod future;
+mod paginator;
+mod query;
+mod retry;
+mod space;
+mod space_builder;
 
 mod hyperdex;
 mod hyperdex_client;
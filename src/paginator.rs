@@ -0,0 +1,327 @@
+use std::mem::transmute;
+
+use rustc_serialize::base64::{FromBase64, ToBase64, URL_SAFE};
+
+use common::*;
+use client::Client;
+use client_types::*;
+use client_types::HyperValue::*;
+use client_types::HyperPredicateType::*;
+
+/// A value that a listing can be sorted on.
+#[derive(Debug, Clone, PartialEq)]
+enum SortValue {
+    Str(Vec<u8>),
+    Int(i64),
+    Float(f64),
+}
+
+impl SortValue {
+    fn from_object(obj: &HyperObject, attr: &str) -> Result<SortValue, HyperError> {
+        match obj.map.get(attr) {
+            Some(&HyperString(ref s)) => Ok(SortValue::Str(s.clone())),
+            Some(&HyperInt(i)) => Ok(SortValue::Int(i)),
+            Some(&HyperFloat(f)) => Ok(SortValue::Float(f)),
+            _ => Err(HyperError::new(HyperErrorKind::InvalidArgument, format!("attribute {} is missing or cannot be sorted on", attr))),
+        }
+    }
+
+    fn to_hyper(&self) -> HyperValue {
+        match *self {
+            SortValue::Str(ref s) => HyperString(s.clone()),
+            SortValue::Int(i) => HyperInt(i),
+            SortValue::Float(f) => HyperFloat(f),
+        }
+    }
+
+    fn encode(&self, raw: &mut Vec<u8>) {
+        match *self {
+            SortValue::Str(ref s) => {
+                raw.push(b's');
+                push_u64(raw, s.len() as u64);
+                raw.push_all(s.as_slice());
+            },
+            SortValue::Int(i) => {
+                raw.push(b'i');
+                push_u64(raw, i as u64);
+            },
+            SortValue::Float(f) => {
+                raw.push(b'f');
+                push_u64(raw, unsafe { transmute::<f64, u64>(f) });
+            },
+        }
+    }
+
+    /// Decodes a value from the front of `raw`, returning it with the rest of `raw`.
+    fn decode(raw: &[u8]) -> Option<(SortValue, &[u8])> {
+        if raw.len() == 0 {
+            return None;
+        }
+        let (tag, rest) = (raw[0], &raw[1..]);
+        let (num, rest) = match read_u64(rest) {
+            Some(x) => x,
+            None => return None,
+        };
+        match tag {
+            b's' if rest.len() as u64 >= num => {
+                let len = num as usize;
+                Some((SortValue::Str(rest[..len].to_vec()), &rest[len..]))
+            },
+            b'i' => Some((SortValue::Int(num as i64), rest)),
+            b'f' => Some((SortValue::Float(unsafe { transmute::<u64, f64>(num) }), rest)),
+            _ => None,
+        }
+    }
+}
+
+fn push_u64(raw: &mut Vec<u8>, num: u64) {
+    for i in 0..8 {
+        raw.push((num >> (56 - 8 * i)) as u8);
+    }
+}
+
+fn read_u64(raw: &[u8]) -> Option<(u64, &[u8])> {
+    if raw.len() < 8 {
+        return None;
+    }
+    let num = raw[..8].iter().fold(0u64, |num, &b| (num << 8) | b as u64);
+    Some((num, &raw[8..]))
+}
+
+/// A position within a paginated listing: the sort value and the key of the last object
+/// returned, along with a fingerprint of the listing it belongs to.
+///
+/// A cursor can be turned into an opaque token with `to_token` and restored with
+/// `from_token`, so that it can be handed to the users of a REST API.  `Paginator::resume`
+/// rejects a cursor that was taken from a different listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    listing: u64,
+    last: SortValue,
+    last_key: SortValue,
+}
+
+impl Cursor {
+    pub fn to_token(&self) -> String {
+        let mut raw = Vec::new();
+        push_u64(&mut raw, self.listing);
+        self.last.encode(&mut raw);
+        self.last_key.encode(&mut raw);
+        raw.as_slice().to_base64(URL_SAFE)
+    }
+
+    pub fn from_token(token: &str) -> Result<Cursor, HyperError> {
+        let invalid = || HyperError::new(HyperErrorKind::InvalidArgument, format!("invalid cursor token: {}", token));
+
+        let raw = match token.from_base64() {
+            Ok(raw) => raw,
+            Err(_) => return Err(invalid()),
+        };
+
+        let (listing, rest) = match read_u64(raw.as_slice()) {
+            Some(x) => x,
+            None => return Err(invalid()),
+        };
+        let (last, rest) = match SortValue::decode(rest) {
+            Some(x) => x,
+            None => return Err(invalid()),
+        };
+        let (last_key, rest) = match SortValue::decode(rest) {
+            Some(x) => x,
+            None => return Err(invalid()),
+        };
+        if rest.len() != 0 {
+            return Err(invalid());
+        }
+
+        Ok(Cursor {
+            listing: listing,
+            last: last,
+            last_key: last_key,
+        })
+    }
+}
+
+/// Walks a space in pages, using keyset pagination over `Client::sorted_search`.
+///
+/// Objects are listed by `sort_by` and then, among objects that share a value of `sort_by`,
+/// by their key in ascending order.  Each page picks up after the (sort value, key) pair of
+/// the last object seen so far, so no object is skipped or repeated even when `sort_by` is
+/// not unique.  HyperDex sorts by a single attribute, so a page that ends among objects
+/// sharing a sort value takes more than one search.
+///
+/// # Examples
+///
+/// ```
+/// let mut pages = client.paginate(space_name, vec!(), "age", "username", 100, false);
+/// while let Some(page) = pages.next_page().unwrap() {
+///     // ...
+/// }
+/// let token = pages.cursor().map(|c| c.to_token());
+/// ```
+pub struct Paginator<'a> {
+    client: &'a mut Client,
+    space: String,
+    checks: Vec<HyperPredicate>,
+    sort_by: String,
+    key_attr: String,
+    page_size: u64,
+    descending: bool,
+    cursor: Option<Cursor>,
+    done: bool,
+}
+
+impl<'a> Paginator<'a> {
+    pub fn new(client: &'a mut Client, space: String, checks: Vec<HyperPredicate>,
+               sort_by: String, key_attr: String, page_size: u64, descending: bool) -> Paginator<'a> {
+        Paginator {
+            client: client,
+            space: space,
+            checks: checks,
+            sort_by: sort_by,
+            key_attr: key_attr,
+            page_size: page_size,
+            descending: descending,
+            cursor: None,
+            done: false,
+        }
+    }
+
+    /// Continues the listing after the given cursor, which must have been taken from a
+    /// listing of the same space with the same predicates, sort attribute and direction.
+    pub fn resume(mut self, cursor: Cursor) -> Result<Paginator<'a>, HyperError> {
+        if cursor.listing != self.listing() {
+            return Err(HyperError::new(HyperErrorKind::InvalidArgument,
+                                       "the cursor belongs to a different listing"));
+        }
+        self.cursor = Some(cursor);
+        Ok(self)
+    }
+
+    /// The position after the last page returned so far.
+    pub fn cursor(&self) -> Option<Cursor> {
+        self.cursor.clone()
+    }
+
+    /// A fingerprint of everything that determines the order of the listing, using 64-bit
+    /// FNV-1a.
+    fn listing(&self) -> u64 {
+        let desc = format!("{}\0{}\0{}\0{}\0{:?}", self.space, self.sort_by, self.key_attr,
+                           self.descending, self.checks);
+        desc.as_bytes().iter().fold(0xcbf29ce484222325u64, |hash, &b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    fn search(&mut self, extra: Vec<HyperPredicate>, sort_by: String, limit: u64, descending: bool)
+        -> Result<Vec<HyperObject>, HyperError> {
+        let mut checks = self.checks.clone();
+        checks.extend(extra.into_iter());
+
+        let mut objs = Vec::new();
+        for res in self.client.sorted_search(self.space.as_str(), checks, sort_by.as_str(),
+                                             limit, descending) {
+            objs.push(try!(res));
+        }
+        Ok(objs)
+    }
+
+    /// The objects that share `value` of the sort attribute and whose key comes after
+    /// `after`, in key order.
+    fn tied(&mut self, value: &SortValue, after: Option<&SortValue>, limit: u64)
+        -> Result<Vec<HyperObject>, HyperError> {
+        let mut extra = vec!(HyperPredicate {
+            attr: self.sort_by.clone(),
+            value: value.to_hyper(),
+            predicate: EQUALS,
+        });
+        if let Some(key) = after {
+            extra.push(HyperPredicate {
+                attr: self.key_attr.clone(),
+                value: key.to_hyper(),
+                predicate: GREATER_THAN,
+            });
+        }
+        let key_attr = self.key_attr.clone();
+        self.search(extra, key_attr, limit, false)
+    }
+
+    /// Fetches the next page, or returns `None` once the listing is exhausted.
+    pub fn next_page(&mut self) -> Result<Option<Vec<HyperObject>>, HyperError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        // Sorting by the key needs no tie-breaking
+        let unique = self.sort_by == self.key_attr;
+        let mut page = Vec::new();
+
+        // The rest of the objects that share the sort value the previous page ended on
+        if let Some(cursor) = self.cursor.clone() {
+            if !unique {
+                page = try!(self.tied(&cursor.last, Some(&cursor.last_key), self.page_size));
+            }
+        }
+
+        let remaining = self.page_size - page.len() as u64;
+        if remaining > 0 {
+            let mut extra = Vec::new();
+            if let Some(ref cursor) = self.cursor {
+                extra.push(HyperPredicate {
+                    attr: self.sort_by.clone(),
+                    value: cursor.last.to_hyper(),
+                    predicate: if self.descending { LESS_THAN } else { GREATER_THAN },
+                });
+            }
+            let (sort_by, descending) = (self.sort_by.clone(), self.descending);
+            let rest = try!(self.search(extra, sort_by, remaining, descending));
+
+            if (rest.len() as u64) < remaining || unique {
+                page.extend(rest.into_iter());
+            } else {
+                // HyperDex orders objects that share a sort value arbitrarily, so the last of
+                // them may have been cut off anywhere.  Fetch those in key order instead.
+                let last = try!(SortValue::from_object(rest.last().unwrap(), self.sort_by.as_str()));
+                for obj in rest.into_iter() {
+                    if try!(SortValue::from_object(&obj, self.sort_by.as_str())) != last {
+                        page.push(obj);
+                    }
+                }
+                let limit = self.page_size - page.len() as u64;
+                page.extend(try!(self.tied(&last, None, limit)).into_iter());
+            }
+        }
+
+        if (page.len() as u64) < self.page_size {
+            self.done = true;
+        }
+        if page.len() == 0 {
+            return Ok(None);
+        }
+
+        let last = try!(SortValue::from_object(page.last().unwrap(), self.sort_by.as_str()));
+        let last_key = try!(SortValue::from_object(page.last().unwrap(), self.key_attr.as_str()));
+        self.cursor = Some(Cursor {
+            listing: self.listing(),
+            last: last,
+            last_key: last_key,
+        });
+
+        Ok(Some(page))
+    }
+}
+
+impl<'a> Iterator for Paginator<'a> {
+    type Item = Result<Vec<HyperObject>, HyperError>;
+
+    fn next(&mut self) -> Option<Result<Vec<HyperObject>, HyperError>> {
+        match self.next_page() {
+            Ok(Some(page)) => Some(Ok(page)),
+            Ok(None) => None,
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
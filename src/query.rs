@@ -0,0 +1,400 @@
+use std::collections::HashSet;
+use std::i64;
+
+use common::*;
+use client::Client;
+use client_types::*;
+use client_types::HyperValue::*;
+use client_types::HyperPredicateType::*;
+
+/// A builder for search predicates.
+///
+/// Each predicate applies to the attribute most recently named with `attr`.  Type errors,
+/// such as a `LENGTH_*` predicate with a non-int value, are reported by `build`.
+///
+/// # Examples
+///
+/// ```
+/// let predicates = Query::new().attr("age").le(25).attr("first").regex("^D").build().unwrap();
+/// let res = client.search(space_name, predicates);
+/// ```
+pub struct Query {
+    predicates: Vec<HyperPredicate>,
+    attr: Option<String>,
+    error: Option<String>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query {
+            predicates: Vec::new(),
+            attr: None,
+            error: None,
+        }
+    }
+
+    /// Sets the attribute that the following predicates apply to.
+    pub fn attr<A>(mut self, attr: A) -> Query where A: ToString {
+        self.attr = Some(attr.to_string());
+        self
+    }
+
+    /// Adds a predicate on the current attribute.
+    pub fn predicate<T>(mut self, predicate: HyperPredicateType, value: T)
+        -> Query where T: TryToHyperValue {
+        if self.error.is_some() {
+            return self;
+        }
+
+        let attr = match self.attr {
+            Some(ref attr) => attr.clone(),
+            None => {
+                self.error = Some(format!("predicate {:?} is not preceded by an attribute", predicate));
+                return self;
+            },
+        };
+
+        match HyperPredicate::new(attr, predicate, value) {
+            Ok(pred) => self.predicates.push(pred),
+            Err(err) => self.error = Some(err.message),
+        }
+        self
+    }
+
+    pub fn equals<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(EQUALS, value)
+    }
+
+    pub fn lt<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(LESS_THAN, value)
+    }
+
+    pub fn le<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(LESS_EQUAL, value)
+    }
+
+    pub fn ge<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(GREATER_EQUAL, value)
+    }
+
+    pub fn gt<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(GREATER_THAN, value)
+    }
+
+    pub fn regex<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(REGEX, value)
+    }
+
+    pub fn length_equals<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(LENGTH_EQUALS, value)
+    }
+
+    pub fn length_le<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(LENGTH_LESS_EQUAL, value)
+    }
+
+    pub fn length_ge<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(LENGTH_GREATER_EQUAL, value)
+    }
+
+    pub fn contains<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(CONTAINS, value)
+    }
+
+    pub fn contains_lt<T>(self, value: T) -> Query where T: TryToHyperValue {
+        self.predicate(CONTAINS_LESS_THAN, value)
+    }
+
+    /// Returns the predicates, or the first type error that was encountered.
+    pub fn build(self) -> Result<Vec<HyperPredicate>, HyperError> {
+        match self.error {
+            Some(err) => Err(HyperError::new(HyperErrorKind::InvalidArgument, err)),
+            None => Ok(self.predicates),
+        }
+    }
+}
+
+/// The largest number of conjunctions that a `QueryExpr` may expand to.
+pub const MAX_CONJUNCTS: usize = 64;
+
+/// A boolean combination of predicates.
+///
+/// HyperDex only supports conjunctions of predicates, so `Client::search_expr` rewrites the
+/// expression into a disjunction of conjunctions and issues one search per conjunction.  An
+/// expression that expands to more than `MAX_CONJUNCTS` conjunctions is rejected.
+///
+/// A negated comparison, such as `NOT age < 18`, is sent to the servers as the opposite
+/// comparison.  For any other negated predicate, the conjunction is searched once more with
+/// the predicate added, and the objects that this search returns are left out of the
+/// conjunction's results.  Either way the servers evaluate every predicate.  A conjunction
+/// made only of negated predicates scans the whole space.
+///
+/// # Examples
+///
+/// ```
+/// let young = QueryExpr::pred(HyperPredicate::new("age", LESS_THAN, 18).unwrap());
+/// let derek = QueryExpr::pred(HyperPredicate::new("first", EQUALS, "Derek").unwrap());
+/// let res = client.search_expr(space_name, "username", young.or(derek.not()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    Pred(HyperPredicate),
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+    Not(Box<QueryExpr>),
+}
+
+// A conjunction of predicates that must match and predicates that must not match.
+type Conjunct = (Vec<HyperPredicate>, Vec<HyperPredicate>);
+
+fn too_complex() -> HyperError {
+    HyperError::new(HyperErrorKind::InvalidArgument,
+                    format!("the expression expands to more than {} searches", MAX_CONJUNCTS))
+}
+
+impl QueryExpr {
+    pub fn pred(predicate: HyperPredicate) -> QueryExpr {
+        QueryExpr::Pred(predicate)
+    }
+
+    /// Matches objects that satisfy every one of the given predicates.
+    pub fn all(predicates: Vec<HyperPredicate>) -> QueryExpr {
+        QueryExpr::And(predicates.into_iter().map(QueryExpr::Pred).collect())
+    }
+
+    pub fn and(self, other: QueryExpr) -> QueryExpr {
+        match self {
+            QueryExpr::And(mut exprs) => {
+                exprs.push(other);
+                QueryExpr::And(exprs)
+            },
+            expr => QueryExpr::And(vec!(expr, other)),
+        }
+    }
+
+    pub fn or(self, other: QueryExpr) -> QueryExpr {
+        match self {
+            QueryExpr::Or(mut exprs) => {
+                exprs.push(other);
+                QueryExpr::Or(exprs)
+            },
+            expr => QueryExpr::Or(vec!(expr, other)),
+        }
+    }
+
+    pub fn not(self) -> QueryExpr {
+        match self {
+            QueryExpr::Not(expr) => *expr,
+            expr => QueryExpr::Not(box expr),
+        }
+    }
+
+    fn to_dnf(&self, negate: bool) -> Result<Vec<Conjunct>, HyperError> {
+        match *self {
+            QueryExpr::Pred(ref pred) => {
+                if !negate {
+                    Ok(vec!((vec!(pred.clone()), Vec::new())))
+                } else {
+                    match invert(pred) {
+                        Some(inverted) => Ok(vec!((vec!(inverted), Vec::new()))),
+                        None => Ok(vec!((Vec::new(), vec!(pred.clone())))),
+                    }
+                }
+            },
+            QueryExpr::Not(ref expr) => expr.to_dnf(!negate),
+            // By De Morgan's laws, a negated AND is an OR of negations and vice versa.
+            QueryExpr::And(ref exprs) if !negate => QueryExpr::dnf_product(exprs, negate),
+            QueryExpr::Or(ref exprs) if negate => QueryExpr::dnf_product(exprs, negate),
+            QueryExpr::And(ref exprs) | QueryExpr::Or(ref exprs) => {
+                let mut dnf = Vec::new();
+                for expr in exprs.iter() {
+                    dnf.extend(try!(expr.to_dnf(negate)).into_iter());
+                    if dnf.len() > MAX_CONJUNCTS {
+                        return Err(too_complex());
+                    }
+                }
+                Ok(dnf)
+            },
+        }
+    }
+
+    fn dnf_product(exprs: &Vec<QueryExpr>, negate: bool) -> Result<Vec<Conjunct>, HyperError> {
+        let mut product = vec!((Vec::new(), Vec::new()));
+        for expr in exprs.iter() {
+            let dnf = try!(expr.to_dnf(negate));
+            if product.len() * dnf.len() > MAX_CONJUNCTS {
+                return Err(too_complex());
+            }
+            let mut next = Vec::new();
+            for &(ref pos, ref neg) in product.iter() {
+                for &(ref other_pos, ref other_neg) in dnf.iter() {
+                    let mut pos = pos.clone();
+                    pos.push_all(other_pos.as_slice());
+                    let mut neg = neg.clone();
+                    neg.push_all(other_neg.as_slice());
+                    next.push((pos, neg));
+                }
+            }
+            product = next;
+        }
+        Ok(product)
+    }
+}
+
+/// The predicate that matches exactly the objects that `pred` does not, if HyperDex has one.
+///
+/// Only comparisons on plain attributes are inverted: every object has every attribute of
+/// its space, but a document may lack the field that a path names.
+fn invert(pred: &HyperPredicate) -> Option<HyperPredicate> {
+    if pred.attr.contains(".") {
+        return None;
+    }
+
+    let predicate = match (pred.predicate, &pred.value) {
+        (LESS_THAN, _) => GREATER_EQUAL,
+        (LESS_EQUAL, _) => GREATER_THAN,
+        (GREATER_EQUAL, _) => LESS_THAN,
+        (GREATER_THAN, _) => LESS_EQUAL,
+        (LENGTH_LESS_EQUAL, &HyperInt(n)) if n < i64::MAX => {
+            return Some(HyperPredicate {
+                attr: pred.attr.clone(),
+                value: HyperInt(n + 1),
+                predicate: LENGTH_GREATER_EQUAL,
+            });
+        },
+        (LENGTH_GREATER_EQUAL, &HyperInt(n)) if n > i64::MIN => {
+            return Some(HyperPredicate {
+                attr: pred.attr.clone(),
+                value: HyperInt(n - 1),
+                predicate: LENGTH_LESS_EQUAL,
+            });
+        },
+        _ => return None,
+    };
+    Some(HyperPredicate {
+        attr: pred.attr.clone(),
+        value: pred.value.clone(),
+        predicate: predicate,
+    })
+}
+
+/// The key of an object, in a form that can be kept in a `HashSet`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Str(Vec<u8>),
+    Int(i64),
+    Float(F64),
+}
+
+impl Key {
+    fn of(obj: &HyperObject, key_attr: &str) -> Result<Key, HyperError> {
+        match obj.map.get(key_attr) {
+            Some(&HyperString(ref s)) => Ok(Key::Str(s.clone())),
+            Some(&HyperInt(i)) => Ok(Key::Int(i)),
+            Some(&HyperFloat(f)) => Ok(Key::Float(F64(f))),
+            _ => Err(HyperError::new(HyperErrorKind::InvalidArgument, format!("the objects found have no key attribute {}", key_attr))),
+        }
+    }
+}
+
+/// The merged results of a `QueryExpr`, as returned by `Client::search_expr`.
+///
+/// Each object is returned once, even if it matches several branches of the expression:
+/// the keys of the objects returned so far are kept, and an object whose key was seen
+/// already is skipped.  The searches are issued one after another as the stream is consumed.
+pub struct QueryStream<'a> {
+    client: &'a mut Client,
+    space: String,
+    key_attr: String,
+    conjuncts: Vec<Conjunct>,
+    // The search of the current conjunct, and the keys that its negations leave out
+    current: Option<SearchStream>,
+    excluded: HashSet<Key>,
+    seen: HashSet<Key>,
+    next: usize,
+    error: Option<HyperError>,
+}
+
+impl<'a> QueryStream<'a> {
+    pub fn new(client: &'a mut Client, space: String, key_attr: String, expr: QueryExpr)
+        -> QueryStream<'a> {
+        let (conjuncts, error) = match expr.to_dnf(false) {
+            Ok(conjuncts) => (conjuncts, None),
+            Err(err) => (Vec::new(), Some(err)),
+        };
+        QueryStream {
+            client: client,
+            space: space,
+            key_attr: key_attr,
+            conjuncts: conjuncts,
+            current: None,
+            excluded: HashSet::new(),
+            seen: HashSet::new(),
+            next: 0,
+            error: error,
+        }
+    }
+
+    /// Starts the search of the next conjunct, after collecting the keys of the objects that
+    /// its negated predicates rule out.
+    fn start_conjunct(&mut self) -> Result<(), HyperError> {
+        let (pos, neg) = self.conjuncts[self.next].clone();
+        self.next += 1;
+
+        self.excluded.clear();
+        for pred in neg.into_iter() {
+            let mut checks = pos.clone();
+            checks.push(pred);
+            for res in self.client.search(self.space.as_str(), checks) {
+                let obj = try!(res);
+                self.excluded.insert(try!(Key::of(&obj, self.key_attr.as_str())));
+            }
+        }
+
+        self.current = Some(self.client.search(self.space.as_str(), pos));
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for QueryStream<'a> {
+    type Item = Result<HyperObject, HyperError>;
+
+    fn next(&mut self) -> Option<Result<HyperObject, HyperError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+
+        loop {
+            if self.current.is_none() {
+                if self.next == self.conjuncts.len() {
+                    return None;
+                }
+                // A conjunct whose negations cannot be checked is skipped
+                if let Err(err) = self.start_conjunct() {
+                    return Some(Err(err));
+                }
+            }
+
+            let res = match self.current {
+                Some(ref mut stream) => stream.next(),
+                None => continue,
+            };
+
+            match res {
+                None => {
+                    self.current = None;
+                },
+                Some(Err(err)) => return Some(Err(err)),
+                Some(Ok(obj)) => {
+                    let key = match Key::of(&obj, self.key_attr.as_str()) {
+                        Ok(key) => key,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    if !self.excluded.contains(&key) && self.seen.insert(key) {
+                        return Some(Ok(obj));
+                    }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+extern crate rand;
+
+use common::*;
+
+/// How a `Client` retries operations that fail with a transient error, i.e. one whose kind
+/// `is_retryable`, such as a reconfiguration or a coordinator failure.
+///
+/// Clients use `RetryPolicy::new()` unless they are given another policy.  Only the blocking
+/// methods retry; the `async_*` methods report the outcome of a single attempt.  An operation
+/// that is not idempotent may already have been applied when it fails with a timeout, so it
+/// is only retried if `retry_non_idempotent` is set.  Besides the atomic operations such as
+/// `atomic_add` and `list_rpush`, this covers `del`, `put_if_not_exist` and the `cond_*`
+/// operations, which would report `NotFound` or `CmpFail` when retried after succeeding.
+///
+/// # Examples
+///
+/// ```
+/// client.set_retry_policy(RetryPolicy::new().max_attempts(5).backoff(20, 2000));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry, in milliseconds.
+    pub initial_backoff_ms: u32,
+
+    /// The longest delay between two attempts, in milliseconds.
+    pub max_backoff_ms: u32,
+
+    /// The factor by which the delay grows after every failed attempt.
+    pub multiplier: f64,
+
+    /// The fraction of each delay that is randomized, from 0 (none) to 1 (all of it).
+    pub jitter: f64,
+
+    /// Whether operations that are not idempotent are retried.
+    pub retry_non_idempotent: bool,
+}
+
+impl RetryPolicy {
+    /// Makes up to 3 attempts, waiting 10ms before the first retry and doubling the delay
+    /// every time, up to 1s.  Half of each delay is randomized.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 1000,
+            multiplier: 2.0,
+            jitter: 0.5,
+            retry_non_idempotent: false,
+        }
+    }
+
+    /// A policy that never retries.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy::new().max_attempts(1)
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> RetryPolicy {
+        self.max_attempts = attempts;
+        self
+    }
+
+    pub fn backoff(mut self, initial_ms: u32, max_ms: u32) -> RetryPolicy {
+        self.initial_backoff_ms = initial_ms;
+        self.max_backoff_ms = max_ms;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> RetryPolicy {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: f64) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn retry_non_idempotent(mut self, retry: bool) -> RetryPolicy {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    /// Whether an operation should be attempted again after its `attempt`th attempt, counting
+    /// from 1, failed with `err`.
+    pub fn should_retry(&self, err: &HyperError, attempt: u32, idempotent: bool) -> bool {
+        attempt < self.max_attempts && err.is_retryable() &&
+            (idempotent || self.retry_non_idempotent)
+    }
+
+    /// The delay after the `attempt`th attempt, counting from 1, before jitter is applied.
+    pub fn backoff_ms(&self, attempt: u32) -> u32 {
+        let exp = if attempt > 0 { attempt - 1 } else { 0 };
+        let delay = self.initial_backoff_ms as f64 * self.multiplier.powi(exp as i32);
+        delay.min(self.max_backoff_ms as f64) as u32
+    }
+
+    /// The delay after the `attempt`th attempt, with a random part of it taken off.
+    pub fn jittered_backoff_ms(&self, attempt: u32) -> u32 {
+        let jitter = self.jitter.max(0.0).min(1.0);
+        let delay = self.backoff_ms(attempt) as f64;
+        (delay * (1.0 - jitter * rand::random::<f64>())) as u32
+    }
+}
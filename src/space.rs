@@ -0,0 +1,532 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Error};
+
+use common::*;
+use hyperdex::*;
+
+use self::HyperDataType::*;
+
+/// Attribute types that HyperDex supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HyperDataType {
+    STRING = HYPERDATATYPE_STRING as isize,
+    INT64 = HYPERDATATYPE_INT64 as isize,
+    FLOAT = HYPERDATATYPE_FLOAT as isize,
+    DOCUMENT = HYPERDATATYPE_DOCUMENT as isize,
+    LIST_STRING = HYPERDATATYPE_LIST_STRING as isize,
+    LIST_INT64 = HYPERDATATYPE_LIST_INT64 as isize,
+    LIST_FLOAT = HYPERDATATYPE_LIST_FLOAT as isize,
+    SET_STRING = HYPERDATATYPE_SET_STRING as isize,
+    SET_INT64 = HYPERDATATYPE_SET_INT64 as isize,
+    SET_FLOAT = HYPERDATATYPE_SET_FLOAT as isize,
+    MAP_STRING_STRING = HYPERDATATYPE_MAP_STRING_STRING as isize,
+    MAP_STRING_INT64 = HYPERDATATYPE_MAP_STRING_INT64 as isize,
+    MAP_STRING_FLOAT = HYPERDATATYPE_MAP_STRING_FLOAT as isize,
+    MAP_INT64_STRING = HYPERDATATYPE_MAP_INT64_STRING as isize,
+    MAP_INT64_INT64 = HYPERDATATYPE_MAP_INT64_INT64 as isize,
+    MAP_INT64_FLOAT = HYPERDATATYPE_MAP_INT64_FLOAT as isize,
+    MAP_FLOAT_STRING = HYPERDATATYPE_MAP_FLOAT_STRING as isize,
+    MAP_FLOAT_INT64 = HYPERDATATYPE_MAP_FLOAT_INT64 as isize,
+    MAP_FLOAT_FLOAT = HYPERDATATYPE_MAP_FLOAT_FLOAT as isize,
+}
+
+impl HyperDataType {
+    /// The name of the type in a space description, e.g. `map(string, int)`.
+    pub fn ddl_name(&self) -> &'static str {
+        match *self {
+            STRING => "string",
+            INT64 => "int",
+            FLOAT => "float",
+            DOCUMENT => "document",
+            LIST_STRING => "list(string)",
+            LIST_INT64 => "list(int)",
+            LIST_FLOAT => "list(float)",
+            SET_STRING => "set(string)",
+            SET_INT64 => "set(int)",
+            SET_FLOAT => "set(float)",
+            MAP_STRING_STRING => "map(string, string)",
+            MAP_STRING_INT64 => "map(string, int)",
+            MAP_STRING_FLOAT => "map(string, float)",
+            MAP_INT64_STRING => "map(int, string)",
+            MAP_INT64_INT64 => "map(int, int)",
+            MAP_INT64_FLOAT => "map(int, float)",
+            MAP_FLOAT_STRING => "map(float, string)",
+            MAP_FLOAT_INT64 => "map(float, int)",
+            MAP_FLOAT_FLOAT => "map(float, float)",
+        }
+    }
+
+    /// Whether the type can be used for the key of a space.
+    pub fn is_primitive(&self) -> bool {
+        match *self {
+            STRING | INT64 | FLOAT => true,
+            _ => false,
+        }
+    }
+
+    fn from_primitive_name(name: &str) -> Option<HyperDataType> {
+        match name {
+            "string" => Some(STRING),
+            "int" | "int64" => Some(INT64),
+            "float" => Some(FLOAT),
+            _ => None,
+        }
+    }
+
+    fn container(container: &str, elem: HyperDataType, value: Option<HyperDataType>)
+        -> Option<HyperDataType> {
+        match (container, elem, value) {
+            ("list", STRING, None) => Some(LIST_STRING),
+            ("list", INT64, None) => Some(LIST_INT64),
+            ("list", FLOAT, None) => Some(LIST_FLOAT),
+            ("set", STRING, None) => Some(SET_STRING),
+            ("set", INT64, None) => Some(SET_INT64),
+            ("set", FLOAT, None) => Some(SET_FLOAT),
+            ("map", STRING, Some(STRING)) => Some(MAP_STRING_STRING),
+            ("map", STRING, Some(INT64)) => Some(MAP_STRING_INT64),
+            ("map", STRING, Some(FLOAT)) => Some(MAP_STRING_FLOAT),
+            ("map", INT64, Some(STRING)) => Some(MAP_INT64_STRING),
+            ("map", INT64, Some(INT64)) => Some(MAP_INT64_INT64),
+            ("map", INT64, Some(FLOAT)) => Some(MAP_INT64_FLOAT),
+            ("map", FLOAT, Some(STRING)) => Some(MAP_FLOAT_STRING),
+            ("map", FLOAT, Some(INT64)) => Some(MAP_FLOAT_INT64),
+            ("map", FLOAT, Some(FLOAT)) => Some(MAP_FLOAT_FLOAT),
+            _ => None,
+        }
+    }
+}
+
+impl Display for HyperDataType {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        Display::fmt(self.ddl_name(), f)
+    }
+}
+
+/// A named, typed attribute of a space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceAttribute {
+    pub name: String,
+    pub datatype: HyperDataType,
+}
+
+impl Display for SpaceAttribute {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        if self.datatype == STRING {
+            Display::fmt(self.name.as_str(), f)
+        } else {
+            write!(f, "{} {}", self.datatype, self.name)
+        }
+    }
+}
+
+/// A description of a space, as accepted by `Admin::add_space`.
+///
+/// `Display` produces the description language that `parse` reads, so a description can
+/// be built in code, printed and parsed back into an equal `SpaceDesc`.
+///
+/// # Examples
+///
+/// ```
+/// let desc = SpaceDesc::new("contacts", "username")
+///     .attribute("first", STRING)
+///     .attribute("age", INT64)
+///     .subspace(vec!("first"))
+///     .partitions(2)
+///     .tolerate(2);
+/// admin.add_space(&desc).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceDesc {
+    pub name: String,
+    pub key: SpaceAttribute,
+    pub attributes: Vec<SpaceAttribute>,
+    pub subspaces: Vec<Vec<String>>,
+    pub indexes: Vec<String>,
+    pub partitions: Option<u64>,
+    pub fault_tolerance: Option<u64>,
+}
+
+impl SpaceDesc {
+    /// Creates a description of a space with a string key and no attributes.
+    pub fn new<S, K>(name: S, key: K) -> SpaceDesc where S: ToString, K: ToString {
+        SpaceDesc {
+            name: name.to_string(),
+            key: SpaceAttribute {
+                name: key.to_string(),
+                datatype: STRING,
+            },
+            attributes: Vec::new(),
+            subspaces: Vec::new(),
+            indexes: Vec::new(),
+            partitions: None,
+            fault_tolerance: None,
+        }
+    }
+
+    pub fn key_type(mut self, datatype: HyperDataType) -> SpaceDesc {
+        self.key.datatype = datatype;
+        self
+    }
+
+    pub fn attribute<A>(mut self, name: A, datatype: HyperDataType) -> SpaceDesc where A: ToString {
+        self.attributes.push(SpaceAttribute {
+            name: name.to_string(),
+            datatype: datatype,
+        });
+        self
+    }
+
+    pub fn subspace<A>(mut self, attrs: Vec<A>) -> SpaceDesc where A: ToString {
+        self.subspaces.push(attrs.iter().map(|a| a.to_string()).collect());
+        self
+    }
+
+    /// Adds an index on an attribute, or on a path inside a document attribute.
+    pub fn index<A>(mut self, attr: A) -> SpaceDesc where A: ToString {
+        self.indexes.push(attr.to_string());
+        self
+    }
+
+    pub fn partitions(mut self, num: u64) -> SpaceDesc {
+        self.partitions = Some(num);
+        self
+    }
+
+    pub fn tolerate(mut self, failures: u64) -> SpaceDesc {
+        self.fault_tolerance = Some(failures);
+        self
+    }
+
+    /// Looks up an attribute, including the key, by name.
+    pub fn get_attribute(&self, name: &str) -> Option<&SpaceAttribute> {
+        if self.key.name == name {
+            return Some(&self.key);
+        }
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    /// Parses a space description such as
+    ///
+    /// ```text
+    /// space contacts
+    /// key username
+    /// attributes first, last, int age
+    /// subspace first, last
+    /// create 2 partitions
+    /// tolerate 2 failures
+    /// ```
+    pub fn parse(desc: &str) -> Result<SpaceDesc, HyperError> {
+        let res = match tokenize(desc) {
+            Ok(tokens) => Parser { tokens: tokens, pos: 0 }.parse_space(),
+            Err(err) => Err(err),
+        };
+        match res.and_then(|space| space.validate().map(|()| space)) {
+            Ok(space) => Ok(space),
+            Err(err) => Err(HyperError::new(HyperErrorKind::Schema, err)),
+        }
+    }
+
+    /// Checks the description for mistakes that HyperDex would reject, such as duplicate
+    /// attributes or subspaces over unknown attributes.
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_identifier(self.name.as_str()) {
+            return Err(format!("invalid space name \"{}\"", self.name));
+        }
+        if !self.key.datatype.is_primitive() {
+            return Err(format!("key {} must be a string, int or float, not {}",
+                               self.key.name, self.key.datatype));
+        }
+
+        let mut names = HashSet::new();
+        names.insert(self.key.name.as_str());
+        for attr in self.attributes.iter() {
+            if !is_identifier(attr.name.as_str()) {
+                return Err(format!("invalid attribute name \"{}\"", attr.name));
+            }
+            if !names.insert(attr.name.as_str()) {
+                return Err(format!("attribute {} is declared more than once", attr.name));
+            }
+        }
+
+        for subspace in self.subspaces.iter() {
+            if subspace.is_empty() {
+                return Err("subspace has no attributes".to_string());
+            }
+            for name in subspace.iter() {
+                if name.as_str() == self.key.name.as_str() {
+                    return Err(format!("subspace cannot contain the key {}", name));
+                }
+                if self.get_attribute(name.as_str()).is_none() {
+                    return Err(format!("subspace refers to unknown attribute {}", name));
+                }
+            }
+        }
+
+        for index in self.indexes.iter() {
+            let mut parts = index.as_str().splitn(2, '.');
+            let attr = parts.next().unwrap_or("");
+            match self.get_attribute(attr) {
+                Some(a) if parts.next().is_some() && a.datatype != DOCUMENT =>
+                    return Err(format!("index {} refers to a path inside {}, which is not a document",
+                                       index, attr)),
+                Some(_) => (),
+                None => return Err(format!("index refers to unknown attribute {}", attr)),
+            }
+        }
+
+        match self.partitions {
+            Some(0) => Err("a space needs at least one partition".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for SpaceDesc {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        try!(write!(f, "space {}\n", self.name));
+        try!(write!(f, "key {}\n", self.key));
+        if !self.attributes.is_empty() {
+            try!(write!(f, "attributes {}\n",
+                        self.attributes.iter().map(|a| a.to_string())
+                            .collect::<Vec<String>>().connect(", ")));
+        }
+        for subspace in self.subspaces.iter() {
+            try!(write!(f, "subspace {}\n", subspace.connect(", ")));
+        }
+        for index in self.indexes.iter() {
+            try!(write!(f, "index {}\n", index));
+        }
+        if let Some(num) = self.partitions {
+            try!(write!(f, "create {} partitions\n", num));
+        }
+        if let Some(num) = self.fault_tolerance {
+            try!(write!(f, "tolerate {} failures\n", num));
+        }
+        Ok(())
+    }
+}
+
+/// Types that can be passed to `Admin::add_space` as the description of a new space.
+pub trait ToSpaceDesc {
+    fn to_space_desc(self) -> Result<String, HyperError>;
+}
+
+impl<'a> ToSpaceDesc for &'a str {
+    fn to_space_desc(self) -> Result<String, HyperError> {
+        Ok(self.to_string())
+    }
+}
+
+impl ToSpaceDesc for String {
+    fn to_space_desc(self) -> Result<String, HyperError> {
+        Ok(self)
+    }
+}
+
+impl<'a> ToSpaceDesc for &'a SpaceDesc {
+    fn to_space_desc(self) -> Result<String, HyperError> {
+        match self.validate() {
+            Ok(()) => Ok(self.to_string()),
+            Err(err) => Err(HyperError::new(HyperErrorKind::Schema, err)),
+        }
+    }
+}
+
+impl ToSpaceDesc for SpaceDesc {
+    fn to_space_desc(self) -> Result<String, HyperError> {
+        (&self).to_space_desc()
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    match s.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' =>
+            s.chars().all(|c| c.is_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+fn tokenize(desc: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in desc.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+            current = String::new();
+        }
+        match c {
+            '(' | ')' | ',' => tokens.push(c.to_string()),
+            _ if c.is_whitespace() => (),
+            _ => return Err(format!("unexpected character '{}' in space description", c)),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(self.pos + offset).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        match self.tokens.get(self.pos) {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t.clone())
+            },
+            None => Err("unexpected end of space description".to_string()),
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = try!(self.next());
+        if token.as_str() == expected {
+            Ok(())
+        } else {
+            Err(format!("expected \"{}\" but found \"{}\"", expected, token))
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, String> {
+        let token = try!(self.next());
+        match token.parse::<u64>() {
+            Ok(num) => Ok(num),
+            Err(_) => Err(format!("expected a number but found \"{}\"", token)),
+        }
+    }
+
+    fn is_section(token: Option<&str>) -> bool {
+        match token {
+            None | Some("key") | Some("attributes") | Some("subspace") | Some("index") |
+            Some("create") | Some("tolerate") => true,
+            _ => false,
+        }
+    }
+
+    fn parse_space(mut self) -> Result<SpaceDesc, String> {
+        try!(self.expect("space"));
+        let name = try!(self.next());
+        try!(self.expect("key"));
+        let key = try!(self.attribute());
+        let mut space = SpaceDesc::new(name, key.name).key_type(key.datatype);
+
+        while let Some(section) = self.peek().map(|s| s.to_string()) {
+            self.pos += 1;
+            match section.as_str() {
+                "attributes" => {
+                    if !Parser::is_section(self.peek()) {
+                        loop {
+                            space.attributes.push(try!(self.attribute()));
+                            if self.peek() != Some(",") {
+                                break;
+                            }
+                            self.pos += 1;
+                        }
+                    }
+                },
+                "subspace" => {
+                    let attrs = try!(self.name_list());
+                    space.subspaces.push(attrs);
+                },
+                "index" => {
+                    let attrs = try!(self.name_list());
+                    space.indexes.extend(attrs.into_iter());
+                },
+                "create" => {
+                    space.partitions = Some(try!(self.number()));
+                    let unit = try!(self.next());
+                    if unit.as_str() != "partitions" && unit.as_str() != "partition" {
+                        return Err(format!("expected \"partitions\" but found \"{}\"", unit));
+                    }
+                },
+                "tolerate" => {
+                    space.fault_tolerance = Some(try!(self.number()));
+                    let unit = try!(self.next());
+                    if unit.as_str() != "failures" && unit.as_str() != "failure" {
+                        return Err(format!("expected \"failures\" but found \"{}\"", unit));
+                    }
+                },
+                _ => return Err(format!("unexpected \"{}\" in space description", section)),
+            }
+        }
+
+        Ok(space)
+    }
+
+    fn name_list(&mut self) -> Result<Vec<String>, String> {
+        let mut names = vec!(try!(self.next()));
+        while self.peek() == Some(",") {
+            self.pos += 1;
+            names.push(try!(self.next()));
+        }
+        Ok(names)
+    }
+
+    /// Parses `[type] name`.  A type is only consumed if it is followed by a name, so an
+    /// untyped attribute may itself be called e.g. `float`.
+    fn attribute(&mut self) -> Result<SpaceAttribute, String> {
+        let typed = match (self.peek(), self.peek_at(1)) {
+            (Some("list"), Some("(")) | (Some("set"), Some("(")) | (Some("map"), Some("(")) => true,
+            (Some(t), Some(next)) =>
+                (HyperDataType::from_primitive_name(t).is_some() || t == "document") &&
+                next != "," && !Parser::is_section(Some(next)),
+            _ => false,
+        };
+
+        let datatype = if typed { try!(self.datatype()) } else { STRING };
+        let name = try!(self.next());
+        Ok(SpaceAttribute {
+            name: name,
+            datatype: datatype,
+        })
+    }
+
+    fn datatype(&mut self) -> Result<HyperDataType, String> {
+        let token = try!(self.next());
+        if token.as_str() == "document" {
+            return Ok(DOCUMENT);
+        }
+        if let Some(t) = HyperDataType::from_primitive_name(token.as_str()) {
+            return Ok(t);
+        }
+
+        try!(self.expect("("));
+        let elem = try!(self.primitive());
+        let value = if token.as_str() == "map" {
+            try!(self.expect(","));
+            Some(try!(self.primitive()))
+        } else {
+            None
+        };
+        try!(self.expect(")"));
+
+        match HyperDataType::container(token.as_str(), elem, value) {
+            Some(t) => Ok(t),
+            None => Err(format!("unknown type {}", token)),
+        }
+    }
+
+    fn primitive(&mut self) -> Result<HyperDataType, String> {
+        let token = try!(self.next());
+        match HyperDataType::from_primitive_name(token.as_str()) {
+            Some(t) => Ok(t),
+            None => Err(format!("expected string, int or float but found \"{}\"", token)),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::ptr::Unique;
+
+use common::*;
+use hyperdex_hyperspace_builder::{Struct_hyperspace, Enum_hyperspace_returncode, HYPERSPACE_SUCCESS};
+use hyperdex_hyperspace_builder::{hyperspace_create, hyperspace_destroy, hyperspace_error,
+                                  hyperspace_set_name, hyperspace_set_key, hyperspace_add_attribute,
+                                  hyperspace_add_subspace, hyperspace_add_subspace_attribute,
+                                  hyperspace_add_index, hyperspace_set_fault_tolerance,
+                                  hyperspace_set_number_of_partitions};
+use space::*;
+
+/// Builds a space description with HyperDex's own space builder, which rejects invalid
+/// names, duplicate attributes, unknown subspace attributes and so on as each call is made.
+///
+/// The finished builder can be passed straight to `Admin::add_space`.
+///
+/// # Examples
+///
+/// ```
+/// let mut builder = SpaceBuilder::new().unwrap();
+/// builder.set_name("contacts").unwrap();
+/// builder.set_key("username", HyperDataType::STRING).unwrap();
+/// builder.add_attribute("age", HyperDataType::INT64).unwrap();
+/// builder.add_subspace(vec!("age")).unwrap();
+/// admin.add_space(builder).unwrap();
+/// ```
+pub struct SpaceBuilder {
+    ptr: Unique<Struct_hyperspace>,
+    desc: SpaceDesc,
+}
+
+impl SpaceBuilder {
+    pub fn new() -> Result<SpaceBuilder, HyperError> {
+        let ptr = unsafe { hyperspace_create() };
+        if ptr.is_null() {
+            Err(HyperError::new(HyperErrorKind::NoMem, "Could not create hyperspace"))
+        } else {
+            Ok(SpaceBuilder {
+                ptr: unsafe { Unique::new(ptr) },
+                desc: SpaceDesc::new("", ""),
+            })
+        }
+    }
+
+    pub fn set_name<S>(&mut self, name: S) -> Result<(), HyperError> where S: ToString {
+        let name = name.to_string();
+        let name_str = try!(name.as_str().to_c_str());
+        try!(self.check(unsafe { hyperspace_set_name(*self.ptr, name_str.as_ptr()) }));
+        self.desc.name = name;
+        Ok(())
+    }
+
+    pub fn set_key<A>(&mut self, attr: A, datatype: HyperDataType)
+        -> Result<(), HyperError> where A: ToString {
+        let attr = attr.to_string();
+        let attr_str = try!(attr.as_str().to_c_str());
+        try!(self.check(unsafe {
+            hyperspace_set_key(*self.ptr, attr_str.as_ptr(), datatype as u32)
+        }));
+        self.desc.key = SpaceAttribute {
+            name: attr,
+            datatype: datatype,
+        };
+        Ok(())
+    }
+
+    pub fn add_attribute<A>(&mut self, attr: A, datatype: HyperDataType)
+        -> Result<(), HyperError> where A: ToString {
+        let attr = attr.to_string();
+        let attr_str = try!(attr.as_str().to_c_str());
+        try!(self.check(unsafe {
+            hyperspace_add_attribute(*self.ptr, attr_str.as_ptr(), datatype as u32)
+        }));
+        self.desc.attributes.push(SpaceAttribute {
+            name: attr,
+            datatype: datatype,
+        });
+        Ok(())
+    }
+
+    /// Adds a subspace over the given attributes.
+    ///
+    /// The attributes are checked before anything is added, so that a rejected subspace
+    /// leaves the builder unchanged.
+    pub fn add_subspace<A>(&mut self, attrs: Vec<A>) -> Result<(), HyperError> where A: ToString {
+        let subspace: Vec<String> = attrs.iter().map(|attr| attr.to_string()).collect();
+        try!(self.check_subspace(&subspace));
+
+        let attr_strs = try!(subspace.iter().map(|attr| attr.as_str().to_c_str())
+                                        .collect::<Result<Vec<_>, _>>());
+        try!(self.check(unsafe { hyperspace_add_subspace(*self.ptr) }));
+        for attr_str in attr_strs.iter() {
+            try!(self.check(unsafe {
+                hyperspace_add_subspace_attribute(*self.ptr, attr_str.as_ptr())
+            }));
+        }
+        self.desc.subspaces.push(subspace);
+        Ok(())
+    }
+
+    pub fn add_index<A>(&mut self, attr: A) -> Result<(), HyperError> where A: ToString {
+        let attr = attr.to_string();
+        let attr_str = try!(attr.as_str().to_c_str());
+        try!(self.check(unsafe { hyperspace_add_index(*self.ptr, attr_str.as_ptr()) }));
+        self.desc.indexes.push(attr);
+        Ok(())
+    }
+
+    pub fn set_fault_tolerance(&mut self, num: u64) -> Result<(), HyperError> {
+        try!(self.check(unsafe { hyperspace_set_fault_tolerance(*self.ptr, num) }));
+        self.desc.fault_tolerance = Some(num);
+        Ok(())
+    }
+
+    pub fn set_number_of_partitions(&mut self, num: u64) -> Result<(), HyperError> {
+        try!(self.check(unsafe { hyperspace_set_number_of_partitions(*self.ptr, num) }));
+        self.desc.partitions = Some(num);
+        Ok(())
+    }
+
+    /// Returns the description built so far, failing if the name or the key is missing.
+    pub fn build(&self) -> Result<SpaceDesc, HyperError> {
+        let missing = if self.desc.name.is_empty() {
+            Some("the space has no name")
+        } else if self.desc.key.name.is_empty() {
+            Some("the space has no key")
+        } else {
+            None
+        };
+
+        let res = match missing {
+            Some(msg) => Err(msg.to_string()),
+            None => self.desc.validate(),
+        };
+        match res {
+            Ok(()) => Ok(self.desc.clone()),
+            Err(err) => Err(HyperError::new(HyperErrorKind::Schema, err)),
+        }
+    }
+
+    /// Checks a subspace against the attributes added so far, as `hyperspace` would.
+    fn check_subspace(&self, subspace: &Vec<String>) -> Result<(), HyperError> {
+        if subspace.is_empty() {
+            return Err(HyperError::new(HyperErrorKind::Schema, "subspace has no attributes"));
+        }
+
+        let mut seen = HashSet::new();
+        for attr in subspace.iter() {
+            let err = if attr.as_str() == self.desc.key.name.as_str() {
+                format!("subspace cannot contain the key {}", attr)
+            } else if self.desc.get_attribute(attr.as_str()).is_none() {
+                format!("subspace refers to unknown attribute {}", attr)
+            } else if !seen.insert(attr.as_str()) {
+                format!("subspace contains {} more than once", attr)
+            } else {
+                continue;
+            };
+            return Err(HyperError::new(HyperErrorKind::Schema, err));
+        }
+        Ok(())
+    }
+
+    fn check(&self, code: Enum_hyperspace_returncode) -> Result<(), HyperError> {
+        if code == HYPERSPACE_SUCCESS {
+            return Ok(());
+        }
+
+        let msg = unsafe { hyperspace_error(*self.ptr) };
+        if msg.is_null() {
+            Err(HyperError::new(HyperErrorKind::Schema, format!("hyperspace returned {}", code)))
+        } else {
+            Err(HyperError::new(HyperErrorKind::Schema, unsafe { to_string(msg) }))
+        }
+    }
+}
+
+impl Drop for SpaceBuilder {
+    fn drop(&mut self) {
+        unsafe {
+            hyperspace_destroy(*self.ptr);
+        }
+    }
+}
+
+impl ToSpaceDesc for SpaceBuilder {
+    fn to_space_desc(self) -> Result<String, HyperError> {
+        self.build().map(|desc| desc.to_string())
+    }
+}
@@ -1,5 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, BTreeMap};
+use std::error::Error;
 use std::str::FromStr;
+use std::thread;
 
 use rustc_serialize::json::Json;
 use rustc_serialize::json::ToJson;
@@ -7,7 +9,10 @@ use rustc_serialize::json::ToJson;
 use super::*;
 use super::HyperValue::*;
 use super::HyperPredicateType::*;
+use super::HyperDataType::*;
 use hyperdex_client::*;
+use hyperdex_admin::*;
+use common::parse_conn_str;
 
 static coord_addr: &'static str = "127.0.0.1:1982";
 
@@ -21,20 +26,62 @@ subspace first, last
 create 2 partitions
 tolerate 2 failures";
 
+/// A space that exists for the duration of a test.  It is removed when the fixture is
+/// dropped, so a failing test does not leave it behind for the next one.
+struct TestSpace {
+    admin: Admin,
+    name: &'static str,
+}
+
+impl TestSpace {
+    /// Creates the space described by `space_desc`.
+    fn new() -> TestSpace {
+        TestSpace::with_desc(space_name, space_desc)
+    }
+
+    fn with_desc(name: &'static str, desc: &str) -> TestSpace {
+        let admin = Admin::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
+        match admin.add_space(desc) {
+            Ok(()) => (),
+            Err(err) => panic!(format!("{}", err)),
+        };
+        TestSpace {
+            admin: admin,
+            name: name,
+        }
+    }
+
+    fn client(&self) -> Client {
+        Client::new(FromStr::from_str(coord_addr).unwrap()).unwrap()
+    }
+
+    /// Puts derek, robert and emin, aged 20, 25 and 30.
+    fn put_people(&self, client: &mut Client) {
+        for &(name, age) in [("derek", 20), ("robert", 25), ("emin", 30)].iter() {
+            client.put(self.name, name, NewHyperObject!("first", name, "age", age,)).unwrap();
+        }
+    }
+}
+
+impl Drop for TestSpace {
+    fn drop(&mut self) {
+        let res = self.admin.remove_space(self.name);
+        // Panicking again while a failed test unwinds would abort the whole run
+        if !thread::panicking() {
+            res.unwrap();
+        }
+    }
+}
+
 #[test]
 fn test_documents() {
-    let admin = Admin::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
-    match admin.add_space("
+    let space = TestSpace::with_desc("profiles", "
 space profiles
 key username
 attributes
-document profile") {
-        Ok(()) => (),
-        Err(err) => panic!(format!("{}", err)),
-    };
-    let space = "profiles";
+document profile");
 
-    let mut client = Client::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
+    let mut client = space.client();
 
     let mut profile: HashMap<String, isize> = HashMap::new();
     profile.insert("name".to_string(), 123);
@@ -43,12 +90,12 @@ document profile") {
     let mut obj = HyperObject::new();
     obj.insert("profile", profile.to_json());
 
-    match client.put(space, "me", obj) {
+    match client.put(space.name, "me", obj) {
         Ok(()) => (),
         Err(err) => panic!(format!("{}", err)),
     }
 
-    match client.get(space, "me") {
+    match client.get(space.name, "me") {
         Ok(mut obj) => {
             let profile: Json = match obj.get("profile") {
                 Ok(s) => s,
@@ -59,7 +106,6 @@ document profile") {
         },
         Err(err) => panic!(format!("{}", err)),
     }
-    admin.remove_space(space).unwrap();
 }
 
 #[test]
@@ -76,31 +122,20 @@ fn test_add_and_rm_space() {
 
 #[test]
 fn test_get_nonexistent_objects() {
-    let admin = Admin::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
-
-    match admin.add_space(space_desc) {
-        Ok(()) => (),
-        Err(err) => panic!(format!("{}", err)),
-    };
+    let space = TestSpace::new();
 
-    let mut client = Client::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
+    let mut client = space.client();
     match client.get(space_name, "lol") {
         Ok(obj) => panic!("wrongly getting an object: {:?}", obj),
-        Err(err) => assert!(err.status == HYPERDEX_CLIENT_NOTFOUND),
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::NotFound),
     }
-
-    admin.remove_space(space_name).unwrap();
 }
 
 #[test]
 fn test_add_and_get_objects() {
-    let admin = Admin::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
-    match admin.add_space(space_desc) {
-        Ok(()) => (),
-        Err(err) => panic!(format!("{}", err)),
-    };
+    let space = TestSpace::new();
 
-    let mut client = Client::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
+    let mut client = space.client();
     match client.put(space_name, "derek", NewHyperObject!(
         "first", "Derek",
         "last", "Chiang",
@@ -126,19 +161,13 @@ fn test_add_and_get_objects() {
         },
         Err(err) => panic!(err),
     }
-
-    admin.remove_space(space_name).unwrap();
 }
 
 #[test]
 fn test_add_and_search_objects() {
-    let admin = Admin::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
-    match admin.add_space(space_desc) {
-        Ok(()) => (),
-        Err(err) => panic!(format!("{}", err)),
-    };
+    let space = TestSpace::new();
 
-    let mut client = Client::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
+    let mut client = space.client();
 
     match client.put(space_name, "derek", NewHyperObject!(
         "first", "Derek",
@@ -169,16 +198,1009 @@ fn test_add_and_search_objects() {
         Err(err) => panic!(err),
     }
 
-    let predicates = vec!(HyperPredicate::new("age", LESS_EQUAL, 25));
+    let predicates = vec!(HyperPredicate::new("age", LESS_EQUAL, 25).unwrap());
 
     let res = client.search(space_name, predicates);
 
-    for obj_res in res.iter() {
+    for obj_res in res {
         let obj = obj_res.unwrap();
         let name: Vec<u8> = obj.get("first").unwrap();
         let age: i64 = obj.get("age").unwrap();
         assert!(age <= 25);
     }
+}
+
+#[test]
+fn test_cond_atomic_add() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    match client.put(space_name, "derek", NewHyperObject!(
+        "first", "Derek",
+        "last", "Chiang",
+        "age", 20,
+    )) {
+        Ok(()) => (),
+        Err(err) => panic!(err),
+    }
+
+    match client.cond_atomic_add(space_name, "derek",
+                                 vec!(HyperPredicate::new("age", EQUALS, 20).unwrap()),
+                                 NewHyperObject!("age", 1,)) {
+        Ok(()) => (),
+        Err(err) => panic!(err),
+    }
+
+    match client.cond_atomic_add(space_name, "derek",
+                                 vec!(HyperPredicate::new("age", EQUALS, 20).unwrap()),
+                                 NewHyperObject!("age", 1,)) {
+        Ok(()) => panic!("the predicate should have failed"),
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::CmpFail),
+    }
+
+    match client.get(space_name, "derek") {
+        Ok(obj) => {
+            let age: i64 = obj.get("age").unwrap();
+            assert_eq!(age, 21);
+        },
+        Err(err) => panic!(err),
+    }
+}
+
+#[test]
+fn test_sorted_search() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    space.put_people(&mut client);
+
+    let res = client.sorted_search(space_name, vec!(), "age", 2, true);
+    let ages: Vec<i64> = res.map(|obj_res| {
+        obj_res.unwrap().get("age").unwrap()
+    }).collect();
+    assert_eq!(ages, vec!(30, 25));
+}
+
+#[test]
+fn test_group_del() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    space.put_people(&mut client);
+
+    let predicates = || vec!(HyperPredicate::new("age", LESS_EQUAL, 25).unwrap());
+    assert_eq!(client.group_del_dry_run(space_name, predicates()).unwrap(), 2);
+    assert_eq!(client.count(space_name, vec!()).unwrap(), 3);
+
+    client.group_del(space_name, predicates()).unwrap();
+    assert_eq!(client.count(space_name, vec!()).unwrap(), 1);
+}
+
+#[test]
+fn test_describe_search() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    let predicates = vec!(HyperPredicate::new("first", EQUALS, "Derek").unwrap());
+    match client.describe_search(space_name, predicates) {
+        Ok(desc) => {
+            assert!(desc.num_servers() > 0);
+            for server in desc.servers.iter() {
+                assert!(server.server.starts_with("server("));
+            }
+        },
+        Err(err) => panic!(err),
+    }
+}
+
+#[test]
+fn test_conn_str() {
+    let admin = Admin::from_conn_str("localhost:1982").unwrap();
+    match admin.add_space(space_desc) {
+        Ok(()) => (),
+        Err(err) => panic!(format!("{}", err)),
+    };
+
+    let mut client = Client::from_conn_str("localhost:1982,127.0.0.1").unwrap();
+    match client.put(space_name, "derek", NewHyperObject!(
+        "first", "Derek",
+    )) {
+        Ok(()) => (),
+        Err(err) => panic!(err),
+    }
+
+    assert!(Client::from_conn_str("localhost:notaport").is_err());
+    assert!(Client::from_conn_str("localhost:1982,").is_err());
 
     admin.remove_space(space_name).unwrap();
 }
+
+#[test]
+fn test_map_remove() {
+    let space = TestSpace::with_desc("maps", "
+space maps
+key id
+attributes map(string, int) counts, map(int, string) names");
+
+    let mut client = space.client();
+
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    counts.insert("a", 1);
+    counts.insert("b", 2);
+    counts.insert("c", 3);
+    let mut names: HashMap<i64, &str> = HashMap::new();
+    names.insert(1, "one");
+    names.insert(2, "two");
+
+    match client.put(space.name, "m", NewHyperObject!(
+        "counts", counts,
+        "names", names,
+    )) {
+        Ok(()) => (),
+        Err(err) => panic!(err),
+    }
+
+    client.map_remove(space.name, "m", "counts", vec!("a", "c")).unwrap();
+    client.map_remove(space.name, "m", "names", vec!(2i64)).unwrap();
+
+    let obj = client.get(space.name, "m").unwrap();
+    let counts: HashMap<Vec<u8>, i64> = obj.get("counts").unwrap();
+    let names: HashMap<i64, Vec<u8>> = obj.get("names").unwrap();
+    assert_eq!(counts.len(), 1);
+    assert_eq!(counts.get("b".as_bytes()), Some(&2));
+    assert_eq!(names.len(), 1);
+    assert_eq!(names.get(&1), Some(&"one".as_bytes().to_vec()));
+}
+
+#[test]
+fn test_drop_search_stream() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    space.put_people(&mut client);
+
+    {
+        let mut res = client.search(space_name, vec!());
+        res.next().unwrap().unwrap();
+    }
+
+    assert_eq!(client.search(space_name, vec!()).count(), 3);
+    client.get(space_name, "derek").unwrap();
+}
+
+#[test]
+fn test_paginate() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    space.put_people(&mut client);
+
+    let token = {
+        let mut pages = client.paginate(space_name, vec!(), "age", "username", 2, false);
+        let page = pages.next_page().unwrap().unwrap();
+        let ages: Vec<i64> = page.iter().map(|obj| obj.get("age").unwrap()).collect();
+        assert_eq!(ages, vec!(20, 25));
+        pages.cursor().unwrap().to_token()
+    };
+
+    let cursor = Cursor::from_token(token.as_str()).unwrap();
+    let pages: Vec<Vec<HyperObject>> = client.paginate(space_name, vec!(), "age", "username", 2, false)
+                                             .resume(cursor.clone())
+                                             .unwrap()
+                                             .map(|page| page.unwrap())
+                                             .collect();
+    assert_eq!(pages.len(), 1);
+    let age: i64 = pages[0][0].get("age").unwrap();
+    assert_eq!(age, 30);
+
+    // A cursor only continues the listing it was taken from
+    assert!(client.paginate(space_name, vec!(), "age", "username", 2, true).resume(cursor.clone()).is_err());
+    assert!(client.paginate(space_name, vec!(), "first", "username", 2, false).resume(cursor).is_err());
+    assert!(Cursor::from_token("bm90IGEgY3Vyc29y").is_err());
+}
+
+#[test]
+fn test_query_builder() {
+    let predicates = Query::new().attr("age").le(25).attr("first").regex("^D").build().unwrap();
+    assert_eq!(predicates, vec!(HyperPredicate::new("age", LESS_EQUAL, 25).unwrap(),
+                                HyperPredicate::new("first", REGEX, "^D").unwrap()));
+
+    let from_macro = predicates!(
+        "age", LESS_EQUAL, 25;
+        "first", REGEX, "^D";
+    ).unwrap();
+    assert_eq!(from_macro, predicates);
+
+    assert!(Query::new().attr("first").length_ge("three").build().is_err());
+    assert!(Query::new().attr("age").regex(25).build().is_err());
+    assert!(Query::new().le(25).build().is_err());
+    assert!(predicates!("age", LENGTH_EQUALS, 2.5;).is_err());
+}
+
+#[test]
+fn test_predicate_validation() {
+    assert!(HyperPredicate::new("first", REGEX, "^D").is_ok());
+    assert!(HyperPredicate::new("first", REGEX, 1).is_err());
+    assert!(HyperPredicate::new("first", LENGTH_EQUALS, 5).is_ok());
+    assert!(HyperPredicate::new("first", LENGTH_LESS_EQUAL, "five").is_err());
+    assert!(HyperPredicate::new("ages", CONTAINS_LESS_THAN, 18).is_ok());
+    assert!(HyperPredicate::new("ages", CONTAINS, vec!(1i64, 2)).is_err());
+}
+
+#[test]
+fn test_search_expr() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    space.put_people(&mut client);
+
+    let young = QueryExpr::pred(HyperPredicate::new("age", LESS_EQUAL, 25).unwrap());
+    let derek = QueryExpr::pred(HyperPredicate::new("first", EQUALS, "derek").unwrap());
+    let old = QueryExpr::pred(HyperPredicate::new("age", GREATER_EQUAL, 30).unwrap());
+
+    let mut names: Vec<Vec<u8>> = client.search_expr(space_name, "username", young.clone().or(old))
+                                        .map(|res| res.unwrap().get("first").unwrap())
+                                        .collect();
+    names.sort();
+    assert_eq!(names, vec!(b"derek".to_vec(), b"emin".to_vec(), b"robert".to_vec()));
+
+    let names: Vec<Vec<u8>> = client.search_expr(space_name, "username", young.clone().and(derek.clone().not()))
+                                    .map(|res| res.unwrap().get("first").unwrap())
+                                    .collect();
+    assert_eq!(names, vec!(b"robert".to_vec()));
+
+    // Overlapping branches return each object once
+    let mut names: Vec<Vec<u8>> = client.search_expr(space_name, "username", young.clone().or(derek.clone()))
+                                        .map(|res| res.unwrap().get("first").unwrap())
+                                        .collect();
+    names.sort();
+    assert_eq!(names, vec!(b"derek".to_vec(), b"robert".to_vec()));
+
+    // Negations on their own, both inverted and searched for and left out
+    let names: Vec<Vec<u8>> = client.search_expr(space_name, "username", young.clone().not())
+                                    .map(|res| res.unwrap().get("first").unwrap())
+                                    .collect();
+    assert_eq!(names, vec!(b"emin".to_vec()));
+
+    let mut names: Vec<Vec<u8>> = client.search_expr(space_name, "username", derek.clone().not())
+                                        .map(|res| res.unwrap().get("first").unwrap())
+                                        .collect();
+    names.sort();
+    assert_eq!(names, vec!(b"emin".to_vec(), b"robert".to_vec()));
+
+    let not_r = QueryExpr::pred(HyperPredicate::new("first", REGEX, "^r").unwrap()).not();
+    let mut names: Vec<Vec<u8>> = client.search_expr(space_name, "username", not_r)
+                                        .map(|res| res.unwrap().get("first").unwrap())
+                                        .collect();
+    names.sort();
+    assert_eq!(names, vec!(b"derek".to_vec(), b"emin".to_vec()));
+
+    // Expressions that expand to too many searches are rejected
+    let mut expr = young.clone().or(derek.clone());
+    for _ in 0..6 {
+        expr = expr.and(young.clone().or(derek.clone()));
+    }
+    let mut res = client.search_expr(space_name, "username", expr);
+    match res.next() {
+        Some(Err(ref err)) if err.kind == HyperErrorKind::InvalidArgument => (),
+        _ => panic!("expected the expression to be rejected"),
+    }
+    assert!(res.next().is_none());
+}
+
+#[test]
+fn test_value_conversions() {
+    let mut tags = HashSet::new();
+    tags.insert("a");
+    tags.insert("b");
+    let mut scores: BTreeMap<i64, f64> = BTreeMap::new();
+    scores.insert(1, 0.5);
+
+    let obj = NewHyperObject!(
+        "name", "Derek",
+        "bad", vec!(0xffu8, 0xfe),
+        "small", 7i32,
+        "big", 1i64 << 40,
+        "negative", -1i64,
+        "flag", true,
+        "tags", tags,
+        "scores", scores.clone(),
+    );
+
+    let name: String = obj.get("name").unwrap();
+    assert_eq!(name, "Derek");
+    match obj.get::<_, String>("bad") {
+        Err(HyperObjectKeyError::InvalidUtf8) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let small: i32 = obj.get("small").unwrap();
+    assert_eq!(small, 7);
+    let big: u64 = obj.get("big").unwrap();
+    assert_eq!(big, 1 << 40);
+    match obj.get::<_, i32>("big") {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match obj.get::<_, u32>("negative") {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let flag: bool = obj.get("flag").unwrap();
+    assert!(flag);
+
+    let missing: Option<i64> = obj.get("missing").unwrap();
+    assert_eq!(missing, None);
+    let present: Option<i32> = obj.get("small").unwrap();
+    assert_eq!(present, Some(7));
+
+    let tags: HashSet<String> = obj.get("tags").unwrap();
+    assert!(tags.contains("a") && tags.contains("b"));
+
+    let read_scores: BTreeMap<i64, f64> = obj.get("scores").unwrap();
+    assert_eq!(read_scores, scores);
+}
+
+#[test]
+fn test_hyperobject_api() {
+    let mut obj: HyperObject = vec!(("first", "Derek"), ("last", "Chiang")).into_iter().collect();
+    obj.extend(vec!(("age", 20i64)).into_iter());
+    assert_eq!(obj.len(), 3);
+    assert!(obj.contains("age"));
+    assert_eq!(obj.get_ref("first").unwrap(), &HyperString(b"Derek".to_vec()));
+
+    let mut keys: Vec<&String> = obj.keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!("age", "first", "last"));
+    assert_eq!((&obj).into_iter().count(), 3);
+
+    obj.merge(NewHyperObject!("age", 21, "city", "Ithaca",));
+    let age: i64 = obj.get("age").unwrap();
+    assert_eq!(age, 21);
+    assert!(obj.contains("city"));
+
+    assert_eq!(obj.remove("city"), Some(HyperString(b"Ithaca".to_vec())));
+    assert!(!obj.contains("city"));
+    assert!(obj.get_ref("city").is_err());
+
+    assert_eq!(obj.into_iter().count(), 3);
+}
+
+#[test]
+fn test_space_desc() {
+    let desc = SpaceDesc::parse(space_desc).unwrap();
+    assert_eq!(desc, SpaceDesc::new("contacts", "username")
+               .attribute("first", STRING)
+               .attribute("last", STRING)
+               .attribute("age", INT64)
+               .subspace(vec!("first", "last"))
+               .partitions(2)
+               .tolerate(2));
+    assert_eq!(SpaceDesc::parse(desc.to_string().as_str()).unwrap(), desc);
+
+    let desc = SpaceDesc::new("profiles", "id")
+        .key_type(INT64)
+        .attribute("profile", DOCUMENT)
+        .attribute("tags", SET_STRING)
+        .attribute("scores", MAP_STRING_FLOAT)
+        .index("profile.age");
+    assert_eq!(SpaceDesc::parse(desc.to_string().as_str()).unwrap(), desc);
+
+    let desc = SpaceDesc::parse("space profiles key username attributes document profile").unwrap();
+    assert_eq!(desc.get_attribute("profile").unwrap().datatype, DOCUMENT);
+
+    assert!(SpaceDesc::parse("space s key k attributes a, a").is_err());
+    assert!(SpaceDesc::parse("space s key k attributes a subspace b").is_err());
+    assert!(SpaceDesc::parse("space s key k attributes a index a.b").is_err());
+    assert!(SpaceDesc::parse("space s key k attributes list(document) a").is_err());
+    assert!(SpaceDesc::parse("space s key document k").is_err());
+}
+
+#[test]
+fn test_space_builder() {
+    let admin = Admin::new(FromStr::from_str(coord_addr).unwrap()).unwrap();
+
+    let mut builder = SpaceBuilder::new().unwrap();
+    builder.set_name(space_name).unwrap();
+    builder.set_key("username", STRING).unwrap();
+    builder.add_attribute("first", STRING).unwrap();
+    builder.add_attribute("last", STRING).unwrap();
+    builder.add_attribute("age", INT64).unwrap();
+    assert!(builder.add_attribute("age", INT64).is_err());
+    assert!(builder.add_subspace(vec!("middle")).is_err());
+    // Rejected subspaces must not leave anything behind in either description
+    assert!(builder.add_subspace(vec!("first", "middle")).is_err());
+    assert!(builder.add_subspace(vec!("first", "first")).is_err());
+    assert!(builder.add_subspace(vec!("username")).is_err());
+    assert!(builder.add_subspace(Vec::<&str>::new()).is_err());
+    builder.add_subspace(vec!("first", "last")).unwrap();
+    builder.set_number_of_partitions(2).unwrap();
+    builder.set_fault_tolerance(2).unwrap();
+    assert_eq!(builder.build().unwrap(), SpaceDesc::parse(space_desc).unwrap());
+
+    match admin.add_space(builder) {
+        Ok(()) => (),
+        Err(err) => panic!(format!("{}", err)),
+    };
+    admin.remove_space(space_name).unwrap();
+
+    assert!(SpaceBuilder::new().unwrap().build().is_err());
+    assert_eq!(SpaceBuilder::new().unwrap().set_name("pro\0files").unwrap_err().kind,
+               HyperErrorKind::Conversion);
+}
+
+#[test]
+fn test_error_kinds() {
+    assert_eq!(HyperErrorKind::from_client_status(HYPERDEX_CLIENT_NOTFOUND), HyperErrorKind::NotFound);
+    assert_eq!(HyperErrorKind::from_admin_status(HYPERDEX_ADMIN_NOTFOUND), HyperErrorKind::NotFound);
+    assert_eq!(HyperErrorKind::from_admin_status(HYPERDEX_ADMIN_DUPLICATE), HyperErrorKind::Duplicate);
+    assert_eq!(HyperErrorKind::from_client_status(1), HyperErrorKind::Unknown(1));
+
+    assert!(HyperErrorKind::from_client_status(HYPERDEX_CLIENT_RECONFIGURE).is_retryable());
+    assert!(HyperErrorKind::from_admin_status(HYPERDEX_ADMIN_TIMEOUT).is_retryable());
+    assert!(!HyperErrorKind::CmpFail.is_retryable());
+
+    let err = HyperPredicate::new("age", REGEX, 1).unwrap_err();
+    assert_eq!(err.kind, HyperErrorKind::InvalidArgument);
+    assert!(!err.is_retryable());
+    assert_eq!(SpaceDesc::parse("space").unwrap_err().kind, HyperErrorKind::Schema);
+
+    let boxed: Box<Error> = Box::new(err);
+    assert!(boxed.description().len() > 0);
+}
+
+#[test]
+fn test_malformed_key_is_an_error() {
+    use std::u64;
+
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    // A key that does not fit in a HyperDex int
+    match client.put(space_name, u64::MAX, NewHyperObject!("first", "Derek",)) {
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::Conversion),
+        Ok(()) => panic!("the put should have failed"),
+    }
+    match client.get(space_name, u64::MAX) {
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::Conversion),
+        Ok(obj) => panic!("unexpected object: {:?}", obj),
+    }
+
+    // An attribute name that cannot be passed to HyperDex
+    match client.put(space_name, "derek", NewHyperObject!("fi\0rst", "Derek",)) {
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::Conversion),
+        Ok(()) => panic!("the put should have failed"),
+    }
+
+    // Neither can a space name with a NUL in it
+    match client.put("pro\0files", "derek", NewHyperObject!("first", "Derek",)) {
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::Conversion),
+        Ok(()) => panic!("the put should have failed"),
+    }
+    assert_eq!(space.admin.remove_space("pro\0files").unwrap_err().kind, HyperErrorKind::Conversion);
+
+    // The client is still usable afterwards
+    client.put(space_name, "derek", NewHyperObject!("first", "Derek",)).unwrap();
+}
+
+#[test]
+fn test_retry_policy() {
+    let timeout = HyperError::new(HyperErrorKind::Timeout, "timed out");
+    let cmpfail = HyperError::new(HyperErrorKind::CmpFail, "comparison failed");
+
+    let policy = RetryPolicy::new().max_attempts(3).backoff(10, 50).jitter(0.0);
+    assert!(policy.should_retry(&timeout, 1, true));
+    assert!(policy.should_retry(&timeout, 2, true));
+    assert!(!policy.should_retry(&timeout, 3, true));
+    assert!(!policy.should_retry(&cmpfail, 1, true));
+    assert!(!policy.should_retry(&timeout, 1, false));
+    assert!(policy.clone().retry_non_idempotent(true).should_retry(&timeout, 1, false));
+    assert!(!RetryPolicy::never().should_retry(&timeout, 1, true));
+
+    assert_eq!(policy.backoff_ms(1), 10);
+    assert_eq!(policy.backoff_ms(2), 20);
+    assert_eq!(policy.backoff_ms(4), 50);
+    assert_eq!(policy.jittered_backoff_ms(2), 20);
+
+    let jittered = policy.jitter(0.5).jittered_backoff_ms(2);
+    assert!(jittered >= 10 && jittered <= 20);
+}
+
+#[test]
+fn test_timeouts() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+    assert_eq!(client.default_timeout(), None);
+
+    client.set_default_timeout(Some(5000));
+    client.put(space_name, "derek", NewHyperObject!("first", "Derek",)).unwrap();
+
+    {
+        let mut timed = client.with_timeout(10000);
+        assert_eq!(timed.default_timeout(), Some(10000));
+        timed.get(space_name, "derek").unwrap();
+    }
+    assert_eq!(client.default_timeout(), Some(5000));
+
+    assert!(!HyperErrorKind::DeadlineExceeded.is_retryable());
+}
+
+#[repr(C)]
+struct PollFd {
+    fd: ::libc::c_int,
+    events: ::libc::c_short,
+    revents: ::libc::c_short,
+}
+
+const POLLIN: ::libc::c_short = 1;
+
+extern {
+    fn poll(fds: *mut PollFd, nfds: ::libc::c_ulong, timeout: ::libc::c_int) -> ::libc::c_int;
+}
+
+/// Runs a reactor client the way an event loop would until nothing is pending, checking that
+/// the client's fd becomes readable whenever no timeout was requested.
+fn drive_reactor(client: &mut Client) {
+    let fd = client.poll_fd().unwrap();
+    while client.pending() > 0 {
+        let timeout = client.process_events();
+        if client.pending() == 0 {
+            break;
+        }
+        let mut pollfd = PollFd { fd: fd, events: POLLIN, revents: 0 };
+        let ready = unsafe {
+            poll(&mut pollfd, 1, timeout.map_or(10000, |ms| ms as ::libc::c_int))
+        };
+        assert!(ready >= 0);
+        if timeout.is_none() {
+            assert!(ready == 1 && pollfd.revents & POLLIN != 0);
+        }
+    }
+}
+
+#[test]
+fn test_reactor_client() {
+    let space = TestSpace::new();
+
+    let threaded = space.client();
+    assert!(threaded.poll_fd().is_none());
+
+    let mut client = ClientBuilder::new()
+        .reactor(true)
+        .search_buffer_size(4)
+        .build_from_conn_str(coord_addr)
+        .unwrap();
+    assert!(client.poll_fd().unwrap() >= 0);
+    assert_eq!(client.pending(), 0);
+    assert_eq!(client.process_events(), None);
+
+    // Blocking methods would never see their reply
+    match client.put(space_name, "derek", NewHyperObject!("first", "Derek",)) {
+        Err(ref err) if err.kind == HyperErrorKind::InvalidArgument => (),
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    let put = client.async_put(space_name, "derek", NewHyperObject!("first", "Derek",));
+    drive_reactor(&mut client);
+    put.into_inner().unwrap();
+
+    let get = client.async_get(space_name, "derek");
+    drive_reactor(&mut client);
+    let obj = get.into_inner().unwrap();
+    let first: Vec<u8> = obj.get("first").unwrap();
+    assert_eq!(first, "Derek".as_bytes());
+
+    // An operation with a timeout makes the event loop wake up by then
+    let put = client.with_timeout(60000).async_put(space_name, "robert", NewHyperObject!("first", "Robert",));
+    if let Some(ms) = client.process_events() {
+        assert!(ms <= 60001);
+    }
+    drive_reactor(&mut client);
+    put.into_inner().unwrap();
+    assert_eq!(client.process_events(), None);
+
+    // More results than the stream buffers, read slowly by another thread
+    let mut puts = Vec::new();
+    for i in 0..20 {
+        puts.push(client.async_put(space_name, format!("user{}", i), NewHyperObject!("age", 1,)));
+    }
+    drive_reactor(&mut client);
+    for put in puts.into_iter() {
+        put.into_inner().unwrap();
+    }
+
+    let predicates = vec!(HyperPredicate::new("age", EQUALS, 1).unwrap());
+    let stream = client.search(space_name, predicates);
+    let reader = thread::spawn(move|| {
+        let mut count = 0;
+        for obj in stream {
+            obj.unwrap();
+            thread::sleep_ms(5);
+            count += 1;
+        }
+        count
+    });
+    drive_reactor(&mut client);
+    assert_eq!(reader.join().unwrap(), 20);
+}
+
+#[test]
+fn test_client_builder() {
+    let _space = TestSpace::new();
+
+    assert!(ClientBuilder::new().inner_clients(0).build_from_conn_str(coord_addr).is_err());
+    assert!(ClientBuilder::new().block_interval(0).build_from_conn_str(coord_addr).is_err());
+    assert!(ClientBuilder::new().reactor(true).inner_clients(2).build_from_conn_str(coord_addr).is_err());
+
+    let mut client = ClientBuilder::new()
+        .inner_clients(2)
+        .thread_name("hyperdex-test")
+        .stack_size(1 << 20)
+        .block_interval(50)
+        .default_timeout(Some(5000))
+        .retry_policy(RetryPolicy::never())
+        .build_from_conn_str(coord_addr)
+        .unwrap();
+    assert_eq!(client.default_timeout(), Some(5000));
+    assert_eq!(client.retry_policy(), &RetryPolicy::never());
+
+    // Idempotent operations are retried by default, others are not
+    let default = ClientBuilder::new().inner_clients(1).build_from_conn_str(coord_addr).unwrap();
+    assert_eq!(default.retry_policy(), &RetryPolicy::new());
+    assert!(!default.retry_policy().retry_non_idempotent);
+
+    client.put(space_name, "derek", NewHyperObject!("first", "Derek",)).unwrap();
+    let obj = client.get(space_name, "derek").unwrap();
+    let first: Vec<u8> = obj.get("first").unwrap();
+    assert_eq!(first, "Derek".as_bytes());
+}
+
+#[test]
+fn test_count() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    space.put_people(&mut client);
+
+    let predicates = vec!(HyperPredicate::new("age", LESS_EQUAL, 25).unwrap());
+    assert_eq!(client.count(space_name, predicates).unwrap(), 2);
+    assert_eq!(client.count(space_name, vec!()).unwrap(), 3);
+}
+
+#[test]
+fn test_parse_search_description() {
+    let desc = SearchDescription::parse("\
+server(1)  considering attr 3 Range: 19 19 int64 [-] valid
+ accessing all objects has cost 4096
+ iterator index_iterator(attr=3) has cost 8
+ choosing to use index_iterator(attr=3)
+ returned 2 objects
+server(2)  accessing all objects has cost 4096
+ choosing to use region_iterator()
+ returned 40 objects
+server(3)  considering attr 3 Range: 20 19 int64 [-] invalid
+ encountered invalid range; returning no objects
+");
+    assert_eq!(desc.num_servers(), 3);
+    assert_eq!(desc.servers[0].server, "server(1)");
+    assert_eq!(desc.servers[0].strategy, SearchStrategy::Index("index_iterator(attr=3)".to_string()));
+    assert_eq!(desc.servers[0].details.len(), 5);
+    assert_eq!(desc.servers[1].strategy, SearchStrategy::FullScan);
+    assert_eq!(desc.servers[2].strategy, SearchStrategy::Empty);
+    assert_eq!(desc.is_full_scan(), Some(true));
+
+    let desc = SearchDescription::parse("\
+server(1)  choosing to use index_iterator(attr=3)
+server(2)  encountered invalid range; returning no objects
+");
+    assert_eq!(desc.is_full_scan(), Some(false));
+
+    // Lines before the first server do not make up a server of their own, and a server
+    // that does not name its iterator leaves the answer open
+    let desc = SearchDescription::parse("searching 2 servers\nserver(7) returned 0 objects\n");
+    assert_eq!(desc.num_servers(), 1);
+    assert_eq!(desc.servers[0].server, "server(7)");
+    assert_eq!(desc.servers[0].strategy, SearchStrategy::Unknown);
+    assert_eq!(desc.is_full_scan(), None);
+    assert_eq!(desc.raw, "searching 2 servers\nserver(7) returned 0 objects\n");
+
+    assert_eq!(SearchDescription::parse("").num_servers(), 0);
+    assert_eq!(SearchDescription::parse("").is_full_scan(), Some(false));
+}
+
+#[test]
+fn test_parse_conn_str() {
+    assert_eq!(parse_conn_str("host1:1983, host2").unwrap(),
+               vec!(("host1".to_string(), 1983), ("host2".to_string(), 1982)));
+    assert_eq!(parse_conn_str("[::1]:1983,[fe80::1]").unwrap(),
+               vec!(("::1".to_string(), 1983), ("fe80::1".to_string(), 1982)));
+
+    assert!(parse_conn_str("::1").is_err());
+    assert!(parse_conn_str("fe80::1:1982").is_err());
+    assert!(parse_conn_str("[::1").is_err());
+    assert!(parse_conn_str("[::1]1982").is_err());
+    assert!(parse_conn_str(":1982").is_err());
+}
+
+#[test]
+fn test_put_while_searching() {
+    let _space = TestSpace::new();
+
+    // A single inner client, so that the puts share it with the search
+    let mut client = ClientBuilder::new()
+        .inner_clients(1)
+        .search_buffer_size(4)
+        .build_from_conn_str(coord_addr)
+        .unwrap();
+
+    for i in 0..50 {
+        client.put(space_name, format!("user{}", i), NewHyperObject!("age", 1,)).unwrap();
+    }
+
+    let mut count = 0;
+    let predicates = vec!(HyperPredicate::new("age", EQUALS, 1).unwrap());
+    for obj in client.search(space_name, predicates) {
+        obj.unwrap();
+        client.put(space_name, format!("other{}", count), NewHyperObject!("age", 2,)).unwrap();
+        count += 1;
+    }
+    assert_eq!(count, 50);
+    assert_eq!(client.count(space_name, vec!(HyperPredicate::new("age", EQUALS, 2).unwrap())).unwrap(), 50);
+}
+
+#[test]
+fn test_paginate_ties() {
+    let space = TestSpace::new();
+
+    let mut client = space.client();
+
+    let people = vec!(("a", 20), ("b", 25), ("c", 25), ("d", 25), ("e", 25), ("f", 30));
+    for &(name, age) in people.iter() {
+        client.put(space_name, name, NewHyperObject!("age", age,)).unwrap();
+    }
+
+    for &page_size in [1, 2, 3, 4].iter() {
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = {
+                let pages = client.paginate(space_name, vec!(), "age", "username", page_size, false);
+                let mut pages = match cursor {
+                    Some(cursor) => pages.resume(cursor).unwrap(),
+                    None => pages,
+                };
+                let page = pages.next_page().unwrap();
+                cursor = pages.cursor();
+                page
+            };
+            match page {
+                Some(page) => {
+                    assert!(page.len() as u64 <= page_size);
+                    for obj in page.iter() {
+                        let name: String = obj.get("username").unwrap();
+                        seen.push(name);
+                    }
+                },
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!("a", "b", "c", "d", "e", "f"));
+    }
+}
+
+#[test]
+fn test_int_ranges() {
+    use std::{i32, u32, i64, u64};
+
+    let obj = NewHyperObject!(
+        "i32_min", i32::MIN,
+        "i32_max", i32::MAX,
+        "u32_max", u32::MAX,
+        "i64_max", i64::MAX,
+        "below_i32", i32::MIN as i64 - 1,
+        "above_u32", u32::MAX as i64 + 1,
+        "negative", -1i64,
+    );
+
+    assert_eq!(obj.get::<_, i32>("i32_min").unwrap(), i32::MIN);
+    assert_eq!(obj.get::<_, i32>("i32_max").unwrap(), i32::MAX);
+    assert_eq!(obj.get::<_, u32>("u32_max").unwrap(), u32::MAX);
+    assert_eq!(obj.get::<_, u64>("i64_max").unwrap(), i64::MAX as u64);
+    for &(attr, ok) in [("below_i32", false), ("u32_max", false), ("negative", true)].iter() {
+        assert_eq!(obj.get::<_, i32>(attr).is_ok(), ok);
+    }
+    for &(attr, ok) in [("above_u32", false), ("negative", false), ("u32_max", true)].iter() {
+        assert_eq!(obj.get::<_, u32>(attr).is_ok(), ok);
+    }
+    match obj.get::<_, u64>("negative") {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // HyperDex ints are signed, so a u64 above i64::MAX cannot be stored
+    assert_eq!((i64::MAX as u64).try_to_hyper().unwrap(), HyperInt(i64::MAX));
+    match (i64::MAX as u64 + 1).try_to_hyper() {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let mut obj = HyperObject::new();
+    match obj.try_insert("big", u64::MAX) {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(obj.map.is_empty());
+    match HyperPredicate::new("age", EQUALS, u64::MAX) {
+        Err(err) => assert_eq!(err.kind, HyperErrorKind::InvalidArgument),
+        Ok(pred) => panic!("unexpected predicate: {:?}", pred),
+    }
+
+    // None is left out of the object and read back as None
+    obj.try_insert("age", Some(20u64)).unwrap();
+    assert_eq!(obj.get::<_, Option<u64>>("age").unwrap(), Some(20));
+    obj.try_insert("age", None::<u64>).unwrap();
+    assert!(obj.map.get("age").is_none());
+    assert_eq!(obj.get::<_, Option<u64>>("age").unwrap(), None);
+    match Some(u64::MAX).try_to_hyper() {
+        Err(HyperObjectKeyError::OutOfRange) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_document_paths() {
+    let space = TestSpace::with_desc("docpaths", "
+space docpaths
+key username
+attributes
+document profile");
+
+    let mut client = space.client();
+
+    let profile = Json::from_str("{\"name\": \"Derek\", \"age\": 20}").unwrap();
+    client.put(space.name, "me", NewHyperObject!("profile", profile,)).unwrap();
+
+    client.doc_atomic_add(space.name, "me", "profile.age", 1).unwrap();
+    client.doc_string_append(space.name, "me", "profile.name", " Chiang").unwrap();
+    client.doc_set(space.name, "me", "profile.city", "Ithaca").unwrap();
+
+    let profile: Json = client.get(space.name, "me").unwrap().get("profile").unwrap();
+    assert_eq!(profile, Json::from_str(
+        "{\"name\": \"Derek Chiang\", \"age\": 21, \"city\": \"Ithaca\"}").unwrap());
+
+    let found = client.count(space.name, vec!(
+        HyperPredicate::doc_path("profile.age", GREATER_EQUAL, 21).unwrap())).unwrap();
+    assert_eq!(found, 1);
+
+    assert!(HyperPredicate::doc_path("profile", EQUALS, 1).is_err());
+    assert!(HyperPredicate::doc_path("profile..age", EQUALS, 1).is_err());
+    assert!(client.doc_atomic_add(space.name, "me", "profile.", 1).is_err());
+}
+
+#[test]
+fn test_expire_ops() {
+    use std::collections::VecDeque;
+    use std::ptr::null;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use client::expire_ops;
+    use client_types::{ExpiredOps, SearchState, EXPIRED_OP_TTL_MS};
+    use client_types::HyperState::*;
+    use future::{event_channel, reply_channel};
+
+    let ms = 1_000_000u64;
+    let now = 1000 * ms;
+    let mut ops = HashMap::new();
+    let mut expired = ExpiredOps::new();
+
+    let (late_tx, late_rx) = reply_channel();
+    let (soon_tx, soon_rx) = reply_channel();
+    let (never_tx, never_rx) = reply_channel();
+    let mut late = HyperFuture::new(late_rx, |_| Ok(()));
+    let mut soon = HyperFuture::new(soon_rx, |_| Ok(()));
+    let mut never = HyperFuture::new(never_rx, |_| Ok(()));
+    ops.insert(1, HyperStateOp(late_tx, box box 0u32, Some(now - ms)));
+    ops.insert(2, HyperStateOp(soon_tx, box box 0u32, Some(now + 5 * ms)));
+    ops.insert(3, HyperStateOp(never_tx, box box 0u32, None));
+
+    let (res_tx, res_rx) = event_channel(4);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    ops.insert(4, HyperStateSearch(SearchState {
+        status: box 0u32,
+        attrs: box AttributePtr(null()),
+        attrs_sz: box 0u64,
+        res_tx: res_tx,
+        cancelled: cancelled.clone(),
+        overflow: VecDeque::new(),
+        overflow_limit: 4,
+        done: false,
+        deadline: Some(now - ms),
+        expired: false,
+    }));
+
+    assert_eq!(expire_ops(&mut ops, &mut expired, now), Some(6));
+
+    // The operation that timed out is failed and its buffers are kept for HyperDex's reply
+    assert_eq!(late.try_get().unwrap().unwrap_err().kind, HyperErrorKind::DeadlineExceeded);
+    assert!(!ops.contains_key(&1));
+    assert_eq!(expired.len(), 1);
+    assert!(soon.try_get().is_none());
+    assert!(never.try_get().is_none());
+
+    // The search that timed out ends its stream with an error, but waits for HyperDex
+    let mut stream = SearchStream::new(res_rx, cancelled);
+    assert_eq!(stream.next().unwrap().err().unwrap().kind, HyperErrorKind::DeadlineExceeded);
+    assert!(stream.next().is_none());
+    assert!(ops.contains_key(&4));
+
+    // Expired searches and operations without a deadline are not waited on
+    ops.remove(&2);
+    assert_eq!(expire_ops(&mut ops, &mut expired, now), None);
+
+    // A late reply frees the buffers
+    assert!(expired.reply(1));
+    assert!(!expired.reply(1));
+    assert_eq!(expired.len(), 0);
+
+    // Requests that are never answered are given up on after a while
+    let (gone_tx, _gone_rx) = reply_channel();
+    ops.insert(5, HyperStateOp(gone_tx, box box 0u32, Some(now)));
+    expire_ops(&mut ops, &mut expired, now);
+    assert_eq!(expired.len(), 1);
+    expire_ops(&mut ops, &mut expired, now + (EXPIRED_OP_TTL_MS - 1) * ms);
+    assert_eq!(expired.len(), 1);
+    expire_ops(&mut ops, &mut expired, now + EXPIRED_OP_TTL_MS * ms);
+    assert_eq!(expired.len(), 0);
+    assert!(!expired.reply(5));
+}
+
+#[test]
+fn test_hyper_future() {
+    use std::sync::mpsc::channel;
+
+    use future::{reply_channel, take_buffers};
+
+    let (reply_tx, reply_rx) = reply_channel();
+    let mut fut = HyperFuture::new(reply_rx, |buffers| take_buffers::<u32>(buffers));
+    assert!(fut.try_get().is_none());
+
+    // The event loop wakes the future once it replies
+    let (ready_tx, ready_rx) = channel();
+    fut.on_ready(move|| ready_tx.send(()).unwrap());
+    assert!(ready_rx.try_recv().is_err());
+    reply_tx.send(Ok(box 7u32));
+    assert!(ready_rx.try_recv().is_ok());
+    assert_eq!(fut.try_get().unwrap().unwrap(), 7);
+    assert_eq!(fut.try_get().unwrap().unwrap_err().kind, HyperErrorKind::Internal);
+
+    // A waiter that comes after the reply is called right away
+    let (reply_tx, reply_rx) = reply_channel();
+    let fut = HyperFuture::new(reply_rx, |buffers| take_buffers::<u32>(buffers));
+    reply_tx.send(Ok(box 8u32));
+    let (ready_tx, ready_rx) = channel();
+    fut.on_ready(move|| ready_tx.send(()).unwrap());
+    assert!(ready_rx.try_recv().is_ok());
+    assert_eq!(fut.into_inner().unwrap(), 8);
+
+    // A request that the event loop drops unanswered fails, and wakes its future
+    let (reply_tx, reply_rx) = reply_channel();
+    let mut fut = HyperFuture::new(reply_rx, |buffers| take_buffers::<u32>(buffers));
+    let (ready_tx, ready_rx) = channel();
+    fut.on_ready(move|| ready_tx.send(()).unwrap());
+    drop(reply_tx);
+    assert!(ready_rx.try_recv().is_ok());
+    assert_eq!(fut.try_get().unwrap().unwrap_err().kind, HyperErrorKind::Internal);
+
+    assert_eq!(HyperFuture::from_value(Ok(9)).into_inner().unwrap(), 9);
+}